@@ -0,0 +1,484 @@
+use std::{env, error::Error, fmt, fmt::Write, fs, path::Path};
+
+const PAGE_SIZE: u64 = 0x1000;
+
+// Section groups of arm64.ld, in link order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Text,
+    Data,
+    Bss,
+    Heap,
+    Stack,
+}
+
+impl Section {
+    const ALL: [Section; 5] = [
+        Section::Text,
+        Section::Data,
+        Section::Bss,
+        Section::Heap,
+        Section::Stack,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Section::Text => "text",
+            Section::Data => "data",
+            Section::Bss => "bss",
+            Section::Heap => "heap",
+            Section::Stack => "stack",
+        }
+    }
+
+    fn offset_symbol(self) -> &'static str {
+        match self {
+            Section::Text => "__TEXT_OFFSET",
+            Section::Data => "__DATA_OFFSET",
+            Section::Bss => "__BSS_OFFSET",
+            Section::Heap => "__HEAP_OFFSET",
+            Section::Stack => "__STACK_OFFSET",
+        }
+    }
+
+    // Symbol at the end of the last output section of the group
+    fn end_symbol(self) -> &'static str {
+        match self {
+            Section::Text => "__rodata_end",
+            Section::Data => "__percpu_end",
+            Section::Bss => "__percpu_area_end",
+            Section::Heap => "__heap_end",
+            Section::Stack => "__emergency_stack_end",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub origin: u64,
+    pub length: u64,
+}
+
+impl MemoryRegion {
+    fn end(&self) -> Result<u64, LayoutError> {
+        self.origin
+            .checked_add(self.length)
+            .ok_or_else(|| LayoutError::RegionOverflow(self.name.clone()))
+    }
+}
+
+type Placement<'a> = Vec<(Section, &'a MemoryRegion)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    NoCpus,
+    TextNotPlaced,
+    UnknownRegion(String),
+    DuplicateRegion(String),
+    EmptyRegion(String),
+    // The region ends beyond the 64-bit address space
+    RegionOverflow(String),
+    Overlap(String, String),
+    Unaligned {
+        name: String,
+        value: u64,
+        align: u64,
+    },
+    // Sections must use a region contiguously, as they follow each other in link order
+    RegionSplit(String),
+    RegionTooSmall {
+        region: String,
+        required: u64,
+        length: u64,
+    },
+    // The text load region must differ from the region text runs in
+    LoadRegionOverlap(String),
+    // The sizes of the sections placed into the region exceed the 64-bit address space
+    SizeOverflow(String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::NoCpus => write!(f, "number of cpus must not be 0"),
+            LayoutError::TextNotPlaced => write!(f, "no region given for the text section"),
+            LayoutError::UnknownRegion(name) => write!(f, "unknown memory region {name}"),
+            LayoutError::DuplicateRegion(name) => write!(f, "memory region {name} defined twice"),
+            LayoutError::EmptyRegion(name) => write!(f, "memory region {name} is empty"),
+            LayoutError::RegionOverflow(name) => {
+                write!(f, "memory region {name} exceeds the 64-bit address space")
+            }
+            LayoutError::Overlap(a, b) => write!(f, "memory regions {a} and {b} overlap"),
+            LayoutError::Unaligned { name, value, align } => {
+                write!(f, "{name} = {value:#x} is not aligned to {align:#x}")
+            }
+            LayoutError::RegionSplit(name) => write!(
+                f,
+                "sections placed into memory region {name} are not contiguous in link order"
+            ),
+            LayoutError::RegionTooSmall {
+                region,
+                required,
+                length,
+            } => write!(
+                f,
+                "memory region {region} ({length:#x} bytes) is too small, at least {required:#x} bytes are required"
+            ),
+            LayoutError::LoadRegionOverlap(name) => write!(
+                f,
+                "text is loaded into memory region {name}, but also runs in it"
+            ),
+            LayoutError::SizeOverflow(name) => write!(
+                f,
+                "sizes of the sections placed into memory region {name} exceed the 64-bit address space"
+            ),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+// Describes the memory layout of an image and generates the memory.ld included by arm64.ld.
+//
+// MemoryLayout::new()
+//     .region("OCM", 0xfffc_0000, 0x4_0000)
+//     .region("DDR", 0x0, 0x8000_0000)
+//     .place(Section::Text, "DDR")
+//     .place(Section::Stack, "OCM")
+//     .load_text("QSPI")
+//     .num_cpu(4)
+//     .stack_size(0x8000)
+//     .heap_size(0x10_0000)
+//     .emit();
+#[derive(Debug, Clone)]
+pub struct MemoryLayout {
+    regions: Vec<MemoryRegion>,
+    placement: Vec<(Section, String)>,
+    text_load: Option<String>,
+    num_cpu: u64,
+    cpu_aff_counts: [Option<u64>; 3],
+    stack_size: u64,
+    stack_guard_size: Option<u64>,
+    exception_stack_size: Option<u64>,
+    emergency_stack_size: Option<u64>,
+    heap_size: u64,
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryLayout {
+    pub fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            placement: Vec::new(),
+            text_load: None,
+            num_cpu: 1,
+            cpu_aff_counts: [None; 3],
+            stack_size: 0x10000,
+            stack_guard_size: None,
+            exception_stack_size: None,
+            emergency_stack_size: None,
+            heap_size: 0,
+        }
+    }
+
+    pub fn region(mut self, name: &str, origin: u64, length: u64) -> Self {
+        self.regions.push(MemoryRegion {
+            name: name.into(),
+            origin,
+            length,
+        });
+        self
+    }
+
+    // Sections without placement follow the previous section into the same region
+    pub fn place(mut self, section: Section, region: &str) -> Self {
+        self.placement.retain(|(s, _)| *s != section);
+        self.placement.push((section, region.into()));
+        self
+    }
+
+    // The image is loaded into this region and text/rodata are copied into their region at startup.
+    // Data placed into another region than text is always loaded with the image and copied.
+    pub fn load_text(mut self, region: &str) -> Self {
+        self.text_load = Some(region.into());
+        self
+    }
+
+    pub fn num_cpu(mut self, num_cpu: u64) -> Self {
+        self.num_cpu = num_cpu;
+        self
+    }
+
+    // Number of values of the MPIDR affinity levels 0-2 used for the dense cpu index
+    pub fn cpu_aff_counts(mut self, aff0: u64, aff1: u64, aff2: u64) -> Self {
+        self.cpu_aff_counts = [Some(aff0), Some(aff1), Some(aff2)];
+        self
+    }
+
+    pub fn cpu_aff0_count(mut self, count: u64) -> Self {
+        self.cpu_aff_counts[0] = Some(count);
+        self
+    }
+
+    pub fn stack_size(mut self, size: u64) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    pub fn stack_guard_size(mut self, size: u64) -> Self {
+        self.stack_guard_size = Some(size);
+        self
+    }
+
+    pub fn exception_stack_size(mut self, size: u64) -> Self {
+        self.exception_stack_size = Some(size);
+        self
+    }
+
+    pub fn emergency_stack_size(mut self, size: u64) -> Self {
+        self.emergency_stack_size = Some(size);
+        self
+    }
+
+    pub fn heap_size(mut self, size: u64) -> Self {
+        self.heap_size = size;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LayoutError> {
+        self.resolve().map(|_| ())
+    }
+
+    pub fn to_linker_script(&self) -> Result<String, LayoutError> {
+        let (placement, text_load) = self.resolve()?;
+
+        let mut script = String::new();
+        let mut w = |line: String| writeln!(script, "{line}").unwrap();
+
+        w("/* Generated by arm64-layout */".into());
+        for region in &self.regions {
+            w(format!(
+                "/* {}: {:#x}..{:#x} */",
+                region.name,
+                region.origin,
+                region.end()?
+            ));
+        }
+        w(String::new());
+
+        w(format!("__NUM_CPU = {};", self.num_cpu));
+        for (level, count) in self.cpu_aff_counts.iter().enumerate() {
+            if let Some(count) = count {
+                w(format!("__CPU_AFF{level}_COUNT = {count};"));
+            }
+        }
+        w(format!("__STACK_SIZE = {:#x};", self.stack_size));
+        if let Some(size) = self.stack_guard_size {
+            w(format!("__STACK_GUARD_SIZE = {size:#x};"));
+        }
+        if let Some(size) = self.exception_stack_size {
+            w(format!("__EXCEPTION_STACK_SIZE = {size:#x};"));
+        }
+        if let Some(size) = self.emergency_stack_size {
+            w(format!("__EMERGENCY_STACK_SIZE = {size:#x};"));
+        }
+        w(format!("__HEAP_SIZE = {:#x};", self.heap_size));
+        w(String::new());
+
+        // Only the first section of each region gets a start address, the others follow it
+        let mut prev_region = None;
+        for (section, region) in &placement {
+            if prev_region != Some(&region.name) {
+                w(format!(
+                    "{} = {:#x};",
+                    section.offset_symbol(),
+                    region.origin
+                ));
+            }
+            prev_region = Some(&region.name);
+        }
+        if let Some(region) = text_load {
+            w(format!("__TEXT_LOAD_OFFSET = {:#x};", region.origin));
+        }
+        w(String::new());
+
+        for (section, region) in &placement {
+            w(format!(
+                "ASSERT({} <= {:#x}, \"{} sections do not fit into memory region {}\")",
+                section.end_symbol(),
+                region.end()?,
+                section.name(),
+                region.name
+            ));
+        }
+
+        // Initial values of data are part of the image, if data is copied at startup
+        let (_, text_region) = placement[0];
+        let (_, data_region) = placement[1];
+        if text_load.is_some() || data_region.name != text_region.name {
+            let image_region = text_load.unwrap_or(text_region);
+            w(format!(
+                "ASSERT(__data_load_end <= {:#x}, \"image does not fit into memory region {}\")",
+                image_region.end()?,
+                image_region.name
+            ));
+        }
+
+        Ok(script)
+    }
+
+    pub fn write(&self, dir: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        fs::write(dir.as_ref().join("memory.ld"), self.to_linker_script()?)?;
+        Ok(())
+    }
+
+    // Writes memory.ld into OUT_DIR and links with arm64.ld, to be called from build.rs
+    pub fn emit(&self) {
+        let out_dir =
+            env::var("OUT_DIR").expect("OUT_DIR not set, emit must be called from build.rs");
+
+        if let Err(err) = self.write(&out_dir) {
+            panic!("invalid memory layout: {err}");
+        }
+
+        println!("cargo:rustc-link-search={out_dir}");
+        println!("cargo:rustc-link-arg=-Tlink.ld");
+    }
+
+    fn resolve(&self) -> Result<(Placement<'_>, Option<&MemoryRegion>), LayoutError> {
+        if self.num_cpu == 0 {
+            return Err(LayoutError::NoCpus);
+        }
+
+        for (i, region) in self.regions.iter().enumerate() {
+            if region.length == 0 {
+                return Err(LayoutError::EmptyRegion(region.name.clone()));
+            }
+            region.end()?;
+
+            for other in &self.regions[..i] {
+                if other.name == region.name {
+                    return Err(LayoutError::DuplicateRegion(region.name.clone()));
+                }
+                if region.origin < other.end()? && other.origin < region.end()? {
+                    return Err(LayoutError::Overlap(
+                        other.name.clone(),
+                        region.name.clone(),
+                    ));
+                }
+            }
+        }
+
+        let guard_size = self.stack_guard_size.unwrap_or(0);
+        check_align("stack guard size", guard_size, PAGE_SIZE)?;
+        check_align("stack size", self.stack_size, 0x10)?;
+        if guard_size != 0 {
+            check_align("stack size", self.stack_size, PAGE_SIZE)?;
+        }
+
+        let exception_stack_size = self.exception_stack_size.unwrap_or(2 * PAGE_SIZE);
+        check_align("exception stack size", exception_stack_size, 0x10)?;
+
+        let emergency_stack_size = self.emergency_stack_size.unwrap_or(PAGE_SIZE);
+        check_align("emergency stack size", emergency_stack_size, 0x10)?;
+
+        let mut placement: Placement = Vec::new();
+        for section in Section::ALL {
+            let region = match self.placement.iter().find(|(s, _)| *s == section) {
+                Some((_, name)) => self.find_region(name)?,
+                None => match placement.last() {
+                    Some((_, region)) => *region,
+                    None => return Err(LayoutError::TextNotPlaced),
+                },
+            };
+
+            match placement.last() {
+                Some((_, prev)) if prev.name == region.name => {}
+                _ => {
+                    if placement.iter().any(|(_, r)| r.name == region.name) {
+                        return Err(LayoutError::RegionSplit(region.name.clone()));
+                    }
+                    check_align(&region.name, region.origin, PAGE_SIZE)?;
+                }
+            }
+
+            placement.push((section, region));
+        }
+
+        // Sizes of text, data and bss are only known to the linker, which checks them with ASSERTs
+        for region in &self.regions {
+            let required = placement
+                .iter()
+                .filter(|(_, r)| r.name == region.name)
+                .try_fold(0u64, |sum, (section, _)| {
+                    let size = match section {
+                        Section::Heap => align_up(self.heap_size, PAGE_SIZE)?,
+                        Section::Stack => [
+                            guard_size.checked_add(self.stack_size),
+                            Some(exception_stack_size),
+                            Some(emergency_stack_size),
+                        ]
+                        .into_iter()
+                        .try_fold(0u64, |sum, size| {
+                            let size = self.num_cpu.checked_mul(size?)?;
+                            sum.checked_add(align_up(size, PAGE_SIZE)?)
+                        })?,
+                        _ => 0,
+                    };
+                    sum.checked_add(size)
+                })
+                .ok_or_else(|| LayoutError::SizeOverflow(region.name.clone()))?;
+
+            if required > region.length {
+                return Err(LayoutError::RegionTooSmall {
+                    region: region.name.clone(),
+                    required,
+                    length: region.length,
+                });
+            }
+        }
+
+        let text_load = match &self.text_load {
+            Some(name) => {
+                let region = self.find_region(name)?;
+                if region.name == placement[0].1.name {
+                    return Err(LayoutError::LoadRegionOverlap(name.clone()));
+                }
+                check_align(&region.name, region.origin, PAGE_SIZE)?;
+                Some(region)
+            }
+            None => None,
+        };
+
+        Ok((placement, text_load))
+    }
+
+    fn find_region(&self, name: &str) -> Result<&MemoryRegion, LayoutError> {
+        self.regions
+            .iter()
+            .find(|region| region.name == name)
+            .ok_or_else(|| LayoutError::UnknownRegion(name.into()))
+    }
+}
+
+fn check_align(name: &str, value: u64, align: u64) -> Result<(), LayoutError> {
+    if !value.is_multiple_of(align) {
+        return Err(LayoutError::Unaligned {
+            name: name.into(),
+            value,
+            align,
+        });
+    }
+
+    Ok(())
+}
+
+fn align_up(value: u64, align: u64) -> Option<u64> {
+    value.checked_next_multiple_of(align)
+}
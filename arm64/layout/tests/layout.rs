@@ -0,0 +1,214 @@
+use arm64_layout::*;
+
+fn qemu() -> MemoryLayout {
+    MemoryLayout::new()
+        .region("DDR", 0x4000_0000, 0x4000_0000)
+        .place(Section::Text, "DDR")
+}
+
+#[test]
+fn memory_ld() {
+    let script = qemu()
+        .num_cpu(4)
+        .cpu_aff0_count(8)
+        .stack_size(0x8000)
+        .heap_size(0x10_0000)
+        .to_linker_script()
+        .unwrap();
+
+    let expected = "\
+/* Generated by arm64-layout */
+/* DDR: 0x40000000..0x80000000 */
+
+__NUM_CPU = 4;
+__CPU_AFF0_COUNT = 8;
+__STACK_SIZE = 0x8000;
+__HEAP_SIZE = 0x100000;
+
+__TEXT_OFFSET = 0x40000000;
+
+ASSERT(__rodata_end <= 0x80000000, \"text sections do not fit into memory region DDR\")
+ASSERT(__percpu_end <= 0x80000000, \"data sections do not fit into memory region DDR\")
+ASSERT(__percpu_area_end <= 0x80000000, \"bss sections do not fit into memory region DDR\")
+ASSERT(__heap_end <= 0x80000000, \"heap sections do not fit into memory region DDR\")
+ASSERT(__emergency_stack_end <= 0x80000000, \"stack sections do not fit into memory region DDR\")
+";
+    assert_eq!(script, expected);
+}
+
+#[test]
+fn stack_sizes() {
+    let script = qemu()
+        .stack_guard_size(0x2000)
+        .exception_stack_size(0x4000)
+        .emergency_stack_size(0x800)
+        .to_linker_script()
+        .unwrap();
+
+    assert!(script.contains("__STACK_GUARD_SIZE = 0x2000;\n"));
+    assert!(script.contains("__EXCEPTION_STACK_SIZE = 0x4000;\n"));
+    assert!(script.contains("__EMERGENCY_STACK_SIZE = 0x800;\n"));
+}
+
+#[test]
+fn placement() {
+    let script = MemoryLayout::new()
+        .region("DDR", 0x0, 0x8000_0000)
+        .region("OCM", 0xfffc_0000, 0x4_0000)
+        .place(Section::Text, "DDR")
+        .place(Section::Stack, "OCM")
+        .to_linker_script()
+        .unwrap();
+
+    assert!(script.contains("__TEXT_OFFSET = 0x0;\n"));
+    assert!(script.contains("__STACK_OFFSET = 0xfffc0000;\n"));
+    assert!(!script.contains("__DATA_OFFSET"));
+    assert!(script.contains("ASSERT(__heap_end <= 0x80000000, "));
+    assert!(script.contains("ASSERT(__emergency_stack_end <= 0x100000000, "));
+
+    // Data copied from the image in DDR into OCM
+    let script = MemoryLayout::new()
+        .region("DDR", 0x0, 0x8000_0000)
+        .region("OCM", 0xfffc_0000, 0x4_0000)
+        .place(Section::Text, "DDR")
+        .place(Section::Data, "OCM")
+        .place(Section::Stack, "OCM")
+        .to_linker_script()
+        .unwrap();
+
+    assert!(script.contains("__DATA_OFFSET = 0xfffc0000;\n"));
+    assert!(script.contains(
+        "ASSERT(__data_load_end <= 0x80000000, \"image does not fit into memory region DDR\")"
+    ));
+
+    assert_eq!(
+        MemoryLayout::new()
+            .region("DDR", 0x0, 0x8000_0000)
+            .region("OCM", 0xfffc_0000, 0x4_0000)
+            .place(Section::Text, "DDR")
+            .place(Section::Data, "OCM")
+            .place(Section::Heap, "DDR")
+            .validate(),
+        Err(LayoutError::RegionSplit("DDR".into()))
+    );
+}
+
+#[test]
+fn rom_xip() {
+    let script = MemoryLayout::new()
+        .region("QSPI", 0xc000_0000, 0x100_0000)
+        .region("DDR", 0x0, 0x8000_0000)
+        .place(Section::Text, "DDR")
+        .load_text("QSPI")
+        .to_linker_script()
+        .unwrap();
+
+    assert!(script.contains("__TEXT_LOAD_OFFSET = 0xc0000000;\n"));
+    assert!(script.contains(
+        "ASSERT(__data_load_end <= 0xc1000000, \"image does not fit into memory region QSPI\")"
+    ));
+
+    assert_eq!(
+        qemu().load_text("DDR").validate(),
+        Err(LayoutError::LoadRegionOverlap("DDR".into()))
+    );
+    assert_eq!(
+        qemu().load_text("QSPI").validate(),
+        Err(LayoutError::UnknownRegion("QSPI".into()))
+    );
+}
+
+#[test]
+fn regions() {
+    assert_eq!(
+        qemu().region("OCM", 0x7fff_0000, 0x2_0000).validate(),
+        Err(LayoutError::Overlap("DDR".into(), "OCM".into()))
+    );
+    assert_eq!(
+        qemu().region("DDR", 0x0, 0x1000).validate(),
+        Err(LayoutError::DuplicateRegion("DDR".into()))
+    );
+    assert_eq!(
+        qemu().region("OCM", 0x0, 0).validate(),
+        Err(LayoutError::EmptyRegion("OCM".into()))
+    );
+    assert_eq!(
+        qemu()
+            .region("TOP", 0xffff_ffff_ffff_f000, 0x1000)
+            .validate(),
+        Err(LayoutError::RegionOverflow("TOP".into()))
+    );
+    assert_eq!(
+        MemoryLayout::new()
+            .region("DDR", 0x4000_0000, 0x4000_0000)
+            .validate(),
+        Err(LayoutError::TextNotPlaced)
+    );
+    assert_eq!(qemu().num_cpu(0).validate(), Err(LayoutError::NoCpus));
+    assert_eq!(
+        qemu()
+            .num_cpu(4)
+            .stack_size(0x4000_0000_0000_0000)
+            .validate(),
+        Err(LayoutError::SizeOverflow("DDR".into()))
+    );
+    assert_eq!(
+        qemu().heap_size(u64::MAX).validate(),
+        Err(LayoutError::SizeOverflow("DDR".into()))
+    );
+    assert_eq!(
+        qemu().heap_size(0x4000_0000).validate(),
+        Err(LayoutError::RegionTooSmall {
+            region: "DDR".into(),
+            required: 0x4001_3000,
+            length: 0x4000_0000,
+        })
+    );
+}
+
+#[test]
+fn alignment() {
+    assert_eq!(
+        MemoryLayout::new()
+            .region("DDR", 0x4000_0800, 0x4000_0000)
+            .place(Section::Text, "DDR")
+            .validate(),
+        Err(LayoutError::Unaligned {
+            name: "DDR".into(),
+            value: 0x4000_0800,
+            align: 0x1000,
+        })
+    );
+    assert_eq!(
+        qemu().stack_size(0x8008).validate(),
+        Err(LayoutError::Unaligned {
+            name: "stack size".into(),
+            value: 0x8008,
+            align: 0x10,
+        })
+    );
+    assert_eq!(
+        qemu()
+            .stack_size(0x8800)
+            .stack_guard_size(0x1000)
+            .validate(),
+        Err(LayoutError::Unaligned {
+            name: "stack size".into(),
+            value: 0x8800,
+            align: 0x1000,
+        })
+    );
+    assert!(qemu().stack_size(0x8800).validate().is_ok());
+    assert!(matches!(
+        qemu().stack_guard_size(0x800).validate(),
+        Err(LayoutError::Unaligned { .. })
+    ));
+    assert!(matches!(
+        qemu().exception_stack_size(0x1008).validate(),
+        Err(LayoutError::Unaligned { .. })
+    ));
+    assert!(matches!(
+        qemu().emergency_stack_size(0x1008).validate(),
+        Err(LayoutError::Unaligned { .. })
+    ));
+}
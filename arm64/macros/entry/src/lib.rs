@@ -33,16 +33,52 @@ pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
         quote!(#arch::DefaultExceptions)
     };
 
+    let target_el = match args.target_el {
+        Some(el @ 1..=2) => quote!(Some(#el)),
+        Some(_) => {
+            return TokenStream::from(Error::custom("target_el must be 1 or 2").write_errors());
+        }
+        None => quote!(None),
+    };
+
+    let fp_enable = args.fp;
+    let park_secondaries = args.spin_table;
+
+    // Linux arm64 Image header, so the raw binary can be booted with U-Boot booti
+    let start = if args.image_header {
+        quote!(::core::arch::naked_asm!(
+            ".globl __image_header",
+            "__image_header:",
+            "b {}",                         // code0
+            ".word 0",                      // code1
+            ".quad __image_text_offset",    // text_offset
+            ".quad __image_size",           // image_size
+            ".quad 0x8",                    // flags: little endian, 2M aligned base anywhere in physical memory
+            ".quad 0, 0, 0",                // res2-4
+            ".ascii \"ARM\\x64\"",      // magic
+            ".word 0",                      // res5
+            sym #arch::start::<crate::EntryImpl, #exceptions>
+        ))
+    } else {
+        quote!(::core::arch::naked_asm!("b {}", sym #arch::start::<crate::EntryImpl, #exceptions>))
+    };
+
     quote!(
         #[unsafe(naked)]
         #[unsafe(no_mangle)]
         #[unsafe(link_section = ".text.start")]
         pub unsafe extern "C" fn _start() -> ! {
-            ::core::arch::naked_asm!("b {}", sym #arch::start::<crate::EntryImpl, #exceptions>)
+            #start
         }
 
+        const _: () = #arch::exceptions::check_fp_context::<#exceptions>(#fp_enable);
+
         struct EntryImpl;
         impl #arch::Entry for EntryImpl {
+            const TARGET_EL: Option<usize> = #target_el;
+            const FP_ENABLE: bool = #fp_enable;
+            const PARK_SECONDARIES: bool = #park_secondaries;
+
             unsafe extern "C" fn entry(info: EntryInfo) -> ! {
                 #f_ident(info)
             }
@@ -83,6 +119,28 @@ pub fn secondary_entry(args: TokenStream, input: TokenStream) -> TokenStream {
         quote!(#arch::DefaultExceptions)
     };
 
+    let target_el = match args.target_el {
+        Some(el @ 1..=2) => quote!(Some(#el)),
+        Some(_) => {
+            return TokenStream::from(Error::custom("target_el must be 1 or 2").write_errors());
+        }
+        None => quote!(None),
+    };
+
+    if args.image_header {
+        return TokenStream::from(
+            Error::custom("image_header is only supported by #[entry]").write_errors(),
+        );
+    }
+
+    if args.spin_table {
+        return TokenStream::from(
+            Error::custom("spin_table is only supported by #[entry]").write_errors(),
+        );
+    }
+
+    let fp_enable = args.fp;
+
     quote!(
         #[unsafe(naked)]
         #[unsafe(no_mangle)]
@@ -90,8 +148,13 @@ pub fn secondary_entry(args: TokenStream, input: TokenStream) -> TokenStream {
             ::core::arch::naked_asm!("b {}", sym #arch::start::<crate::SecondaryEntryImpl, #exceptions>)
         }
 
+        const _: () = #arch::exceptions::check_fp_context::<#exceptions>(#fp_enable);
+
         struct SecondaryEntryImpl;
         impl #arch::Entry for SecondaryEntryImpl {
+            const TARGET_EL: Option<usize> = #target_el;
+            const FP_ENABLE: bool = #fp_enable;
+
             unsafe extern "C" fn entry(info: EntryInfo) -> ! {
                 #f_ident(info)
             }
@@ -105,4 +168,11 @@ pub fn secondary_entry(args: TokenStream, input: TokenStream) -> TokenStream {
 #[derive(Debug, FromMeta)]
 struct MacroArgs {
     exceptions: Option<Ident>,
+    target_el: Option<usize>,
+    #[darling(default)]
+    fp: bool,
+    #[darling(default)]
+    image_header: bool,
+    #[darling(default)]
+    spin_table: bool,
 }
@@ -33,3 +33,12 @@ macro_rules! isb {
         }
     };
 }
+
+#[macro_export]
+macro_rules! sev {
+    () => {
+        unsafe {
+            core::arch::asm!("sev");
+        }
+    };
+}
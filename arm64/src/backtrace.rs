@@ -0,0 +1,95 @@
+use core::{arch::asm, ops::Range};
+
+use crate::{
+    exceptions::ExceptionFrame,
+    stack::{emergency_stack_range, exception_stack_range, stack_range},
+    start::cpu_idx,
+    user::user_stack_range,
+};
+
+pub struct Backtrace {
+    fp: usize,
+    stack: Range<usize>,
+    regs: [Option<usize>; 2],
+}
+
+impl Backtrace {
+    pub fn new(fp: usize, stack: Range<usize>) -> Self {
+        Self {
+            fp,
+            stack,
+            regs: [None; 2],
+        }
+    }
+
+    #[inline(always)]
+    pub fn current() -> Self {
+        let fp: usize;
+        unsafe {
+            asm!("mov {}, x29", out(reg) fp);
+        }
+
+        Self::new(fp, core_stack(fp).unwrap_or(0..0))
+    }
+
+    // Yields elr and lr (the caller of a leaf function without frame record) before the frame records
+    pub fn from_frame(frame: &ExceptionFrame) -> Self {
+        let fp = frame.fp as usize;
+
+        // Exceptions from EL0 (SPSR.M = EL0t) interrupted a user task on its own stack
+        let stack = if frame.spsr & 0x1f == 0 {
+            user_stack_range(frame.sp as usize).filter(|stack| stack.contains(&fp))
+        } else {
+            core_stack(fp)
+        };
+
+        Self {
+            regs: [Some(frame.elr as usize), Some(frame.lr as usize)],
+            ..Self::new(fp, stack.unwrap_or(0..0))
+        }
+    }
+}
+
+// Stack of this core containing fp: main, exception or emergency stack
+fn core_stack(fp: usize) -> Option<Range<usize>> {
+    let cpu_idx = cpu_idx();
+
+    [
+        stack_range(cpu_idx),
+        exception_stack_range(cpu_idx),
+        emergency_stack_range(cpu_idx),
+    ]
+    .into_iter()
+    .find(|stack| stack.contains(&fp))
+}
+
+impl Iterator for Backtrace {
+    type Item = usize;
+
+    // Walks the frame records [fp] = prev fp, [fp + 8] = return address
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(addr) = self.regs.iter_mut().find_map(Option::take) {
+            return Some(addr);
+        }
+
+        let fp = self.fp;
+
+        if fp % 8 != 0 || fp < self.stack.start || fp + 16 > self.stack.end {
+            return None;
+        }
+
+        let (prev_fp, ret_addr) = unsafe {
+            let record = fp as *const usize;
+            (record.read_volatile(), record.add(1).read_volatile())
+        };
+
+        if ret_addr == 0 {
+            return None;
+        }
+
+        // Frame records must move up the stack, otherwise the chain is corrupt
+        self.fp = if prev_fp > fp { prev_fp } else { 0 };
+
+        Some(ret_addr)
+    }
+}
@@ -1,5 +1,15 @@
+mod crash_dump;
+mod syndrome;
+mod syscall;
+
 use cfg_asm::cfg_naked_asm;
-use core::arch::naked_asm;
+use core::{arch::naked_asm, mem::offset_of};
+
+use crate::stack::ExceptionStackCtx;
+
+pub use crash_dump::*;
+pub use syndrome::*;
+pub use syscall::*;
 
 #[allow(non_camel_case_types)]
 pub struct ELx_SP_EL0;
@@ -10,7 +20,16 @@ pub struct ELy_AARCH64;
 #[allow(non_camel_case_types)]
 pub struct ELy_AARCH32;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FpContext {
+    None,
+    Eager,
+    Lazy,
+}
+
 pub trait Exceptions<EL> {
+    const FP_CONTEXT: FpContext = FpContext::None;
+
     fn sync_excp(_frame: &mut ExceptionFrame) {
         loop {}
     }
@@ -29,25 +48,64 @@ pub trait Exceptions<EL> {
 }
 
 pub trait ExceptionVectors {
-    unsafe extern "C" fn sync_excp_elx_sp_el0() -> !;
-    unsafe extern "C" fn irq_elx_sp_el0() -> !;
-    unsafe extern "C" fn fiq_elx_sp_el0() -> !;
-    unsafe extern "C" fn serror_elx_sp_el0() -> !;
-
-    unsafe extern "C" fn sync_excp_elx_sp_elx() -> !;
-    unsafe extern "C" fn irq_elx_sp_elx() -> !;
-    unsafe extern "C" fn fiq_elx_sp_elx() -> !;
-    unsafe extern "C" fn serror_elx_sp_elx() -> !;
-
-    unsafe extern "C" fn sync_excp_ely_aarch64() -> !;
-    unsafe extern "C" fn irq_ely_aarch64() -> !;
-    unsafe extern "C" fn fiq_ely_aarch64() -> !;
-    unsafe extern "C" fn serror_ely_aarch64() -> !;
-
-    unsafe extern "C" fn sync_excp_ely_aarch32() -> !;
-    unsafe extern "C" fn irq_ely_aarch32() -> !;
-    unsafe extern "C" fn fiq_ely_aarch32() -> !;
-    unsafe extern "C" fn serror_ely_aarch32() -> !;
+    unsafe extern "C" fn sync_excp_elx_sp_el0<const EL: u8>() -> !;
+    unsafe extern "C" fn irq_elx_sp_el0<const EL: u8>() -> !;
+    unsafe extern "C" fn fiq_elx_sp_el0<const EL: u8>() -> !;
+    unsafe extern "C" fn serror_elx_sp_el0<const EL: u8>() -> !;
+
+    unsafe extern "C" fn sync_excp_elx_sp_elx<const EL: u8>() -> !;
+    unsafe extern "C" fn irq_elx_sp_elx<const EL: u8>() -> !;
+    unsafe extern "C" fn fiq_elx_sp_elx<const EL: u8>() -> !;
+    unsafe extern "C" fn serror_elx_sp_elx<const EL: u8>() -> !;
+
+    unsafe extern "C" fn sync_excp_ely_aarch64<const EL: u8>() -> !;
+    unsafe extern "C" fn irq_ely_aarch64<const EL: u8>() -> !;
+    unsafe extern "C" fn fiq_ely_aarch64<const EL: u8>() -> !;
+    unsafe extern "C" fn serror_ely_aarch64<const EL: u8>() -> !;
+
+    unsafe extern "C" fn sync_excp_ely_aarch32<const EL: u8>() -> !;
+    unsafe extern "C" fn irq_ely_aarch32<const EL: u8>() -> !;
+    unsafe extern "C" fn fiq_ely_aarch32<const EL: u8>() -> !;
+    unsafe extern "C" fn serror_ely_aarch32<const EL: u8>() -> !;
+}
+
+// FP context saving needs FP access enabled at startup (#[entry(fp)]), otherwise eager vectors trap on
+// their own save. Lazy ownership tracking does not see eager vectors, so both can not be mixed.
+#[doc(hidden)]
+pub const fn check_fp_context<T>(fp_enable: bool)
+where
+    T: Exceptions<ELx_SP_EL0>
+        + Exceptions<ELx_SP_ELx>
+        + Exceptions<ELy_AARCH64>
+        + Exceptions<ELy_AARCH32>,
+{
+    let contexts = [
+        <T as Exceptions<ELx_SP_EL0>>::FP_CONTEXT,
+        <T as Exceptions<ELx_SP_ELx>>::FP_CONTEXT,
+        <T as Exceptions<ELy_AARCH64>>::FP_CONTEXT,
+        <T as Exceptions<ELy_AARCH32>>::FP_CONTEXT,
+    ];
+
+    let mut eager = false;
+    let mut lazy = false;
+    let mut i = 0;
+    while i < contexts.len() {
+        match contexts[i] {
+            FpContext::None => {}
+            FpContext::Eager => eager = true,
+            FpContext::Lazy => lazy = true,
+        }
+        i += 1;
+    }
+
+    assert!(
+        fp_enable || !(eager || lazy),
+        "FP context saving requires FP to be enabled with #[entry(fp)]"
+    );
+    assert!(
+        !(eager && lazy),
+        "eager and lazy FP context saving can not be mixed"
+    );
 }
 
 pub struct DefaultExceptions;
@@ -57,6 +115,7 @@ impl Exceptions<ELx_SP_ELx> for DefaultExceptions {}
 impl Exceptions<ELy_AARCH64> for DefaultExceptions {}
 impl Exceptions<ELy_AARCH32> for DefaultExceptions {}
 
+#[repr(C)]
 pub struct ExceptionFrame {
     pub x0: u64,
     pub x1: u64,
@@ -77,15 +136,55 @@ pub struct ExceptionFrame {
     pub x16: u64,
     pub x17: u64,
     pub x18: u64,
+    pub x19: u64,
+    pub x20: u64,
+    pub x21: u64,
+    pub x22: u64,
+    pub x23: u64,
+    pub x24: u64,
+    pub x25: u64,
+    pub x26: u64,
+    pub x27: u64,
+    pub x28: u64,
     pub fp: u64,
     pub lr: u64,
-    pub xzr: u64,
+    pub sp: u64,
+    pub elr: u64,
+    pub spsr: u64,
+    pub esr: u64,
+    pub far: u64,
+}
+
+impl ExceptionFrame {
+    pub fn exception_class(&self) -> ExceptionClass {
+        ExceptionClass::from(self.esr)
+    }
+
+    pub fn syndrome(&self) -> Syndrome {
+        Syndrome::from(self.esr)
+    }
+}
+
+const _: () = assert!(size_of::<ExceptionFrame>() == 0x120);
+const _: () = assert!(offset_of!(ExceptionFrame, fp) == 0xe8);
+const _: () = assert!(offset_of!(ExceptionFrame, sp) == 0xf8);
+const _: () = assert!(offset_of!(ExceptionFrame, elr) == 0x100);
+const _: () = assert!(offset_of!(ExceptionFrame, esr) == 0x110);
+
+#[repr(C)]
+pub struct FpFrame {
+    pub fpcr: u64,
+    pub fpsr: u64,
+    pub trap: u64,
+    pub saved: u64,
+    pub q: [u128; 32],
+    pub prev_owner: u64, // Lazy: FP owner of the interrupted context
 }
 
 #[unsafe(naked)]
 #[unsafe(link_section = ".text.vector_table")]
-// #[repr(align(2048))]
-pub unsafe extern "C" fn vector_table<ExcpVecs>() -> !
+#[rustc_align(2048)]
+pub unsafe extern "C" fn vector_table<ExcpVecs, const EL: u8>() -> !
 where
     ExcpVecs: ExceptionVectors,
 {
@@ -138,78 +237,361 @@ where
         ".balign 0x80",
         "b {serror_ely_aarch32}",
     },
-        sync_excp_elx_sp_el0 = sym ExcpVecs::sync_excp_elx_sp_el0,
-        irq_elx_sp_el0 = sym  ExcpVecs::irq_elx_sp_el0,
-        fiq_elx_sp_el0 = sym  ExcpVecs::fiq_elx_sp_el0,
-        serror_elx_sp_el0 = sym ExcpVecs::serror_elx_sp_el0,
-
-        sync_excp_elx_sp_elx = sym ExcpVecs::sync_excp_elx_sp_elx,
-        irq_elx_sp_elx = sym ExcpVecs::irq_elx_sp_elx,
-        fiq_elx_sp_elx = sym ExcpVecs::fiq_elx_sp_elx,
-        serror_elx_sp_elx = sym ExcpVecs::serror_elx_sp_elx,
-
-        sync_excp_ely_aarch64 = sym ExcpVecs::sync_excp_ely_aarch64,
-        irq_ely_aarch64 = sym ExcpVecs::irq_ely_aarch64,
-        fiq_ely_aarch64 = sym ExcpVecs::fiq_ely_aarch64,
-        serror_ely_aarch64 = sym ExcpVecs::serror_ely_aarch64,
-
-        sync_excp_ely_aarch32 = sym ExcpVecs::sync_excp_ely_aarch32,
-        irq_ely_aarch32 = sym ExcpVecs::irq_ely_aarch32,
-        fiq_ely_aarch32 = sym ExcpVecs::fiq_ely_aarch32,
-        serror_ely_aarch32 = sym ExcpVecs::serror_ely_aarch32,
+        sync_excp_elx_sp_el0 = sym ExcpVecs::sync_excp_elx_sp_el0::<EL>,
+        irq_elx_sp_el0 = sym ExcpVecs::irq_elx_sp_el0::<EL>,
+        fiq_elx_sp_el0 = sym ExcpVecs::fiq_elx_sp_el0::<EL>,
+        serror_elx_sp_el0 = sym ExcpVecs::serror_elx_sp_el0::<EL>,
+
+        sync_excp_elx_sp_elx = sym ExcpVecs::sync_excp_elx_sp_elx::<EL>,
+        irq_elx_sp_elx = sym ExcpVecs::irq_elx_sp_elx::<EL>,
+        fiq_elx_sp_elx = sym ExcpVecs::fiq_elx_sp_elx::<EL>,
+        serror_elx_sp_elx = sym ExcpVecs::serror_elx_sp_elx::<EL>,
+
+        sync_excp_ely_aarch64 = sym ExcpVecs::sync_excp_ely_aarch64::<EL>,
+        irq_ely_aarch64 = sym ExcpVecs::irq_ely_aarch64::<EL>,
+        fiq_ely_aarch64 = sym ExcpVecs::fiq_ely_aarch64::<EL>,
+        serror_ely_aarch64 = sym ExcpVecs::serror_ely_aarch64::<EL>,
+
+        sync_excp_ely_aarch32 = sym ExcpVecs::sync_excp_ely_aarch32::<EL>,
+        irq_ely_aarch32 = sym ExcpVecs::irq_ely_aarch32::<EL>,
+        fiq_ely_aarch32 = sym ExcpVecs::fiq_ely_aarch32::<EL>,
+        serror_ely_aarch32 = sym ExcpVecs::serror_ely_aarch32::<EL>,
     )
 }
 
 macro_rules! save_regs {
     () => {
-        "stp x30, xzr, [sp, #-16]!
-         stp x18, x29, [sp, #-16]!
-         stp x16, x17, [sp, #-16]!
-         stp x14, x15, [sp, #-16]!
-         stp x12, x13, [sp, #-16]!
-         stp x10, x11, [sp, #-16]!
-         stp x8, x9, [sp, #-16]!
-         stp x6, x7, [sp, #-16]!
-         stp x4, x5, [sp, #-16]!
-         stp x2, x3, [sp, #-16]!
-         stp x0, x1, [sp, #-16]!"
+        "sub sp, sp, #{frame_size}
+         stp x0, x1, [sp, #0x00]
+         stp x2, x3, [sp, #0x10]
+         stp x4, x5, [sp, #0x20]
+         stp x6, x7, [sp, #0x30]
+         stp x8, x9, [sp, #0x40]
+         stp x10, x11, [sp, #0x50]
+         stp x12, x13, [sp, #0x60]
+         stp x14, x15, [sp, #0x70]
+         stp x16, x17, [sp, #0x80]
+         stp x18, x19, [sp, #0x90]
+         stp x20, x21, [sp, #0xa0]
+         stp x22, x23, [sp, #0xb0]
+         stp x24, x25, [sp, #0xc0]
+         stp x26, x27, [sp, #0xd0]
+         stp x28, x29, [sp, #0xe0]
+         str x30, [sp, #0xf0]"
+    };
+}
+
+macro_rules! save_sys_regs {
+    () => {
+        "mrs x0, ELR_EL{el}
+         mrs x1, SPSR_EL{el}
+         stp x0, x1, [sp, #0x100]
+         mrs x2, ESR_EL{el}
+         mrs x3, FAR_EL{el}
+         stp x2, x3, [sp, #0x110]
+         mov x0, #0
+         tbnz x1, #4, 3f
+         tbz x1, #0, 1f
+         ubfx x2, x1, #2, #2
+         cmp x2, #{el}
+         b.eq 2f
+         .if {el} == 2
+         mrs x0, SP_EL1
+         .elseif {el} == 3
+         cmp x2, #0x1
+         b.ne 4f
+         mrs x0, SP_EL1
+         b 3f
+         4:
+         mrs x0, SP_EL2
+         .endif
+         b 3f
+         1:
+         mrs x0, SP_EL0
+         b 3f
+         2:
+         add x0, sp, #{frame_size}
+         3:
+         str x0, [sp, #0xf8]"
     };
 }
 
 macro_rules! restore_regs {
     () => {
-        "ldp x0, x1, [sp], #16
-         ldp x2, x3, [sp], #16
-         ldp x4, x5, [sp], #16
-         ldp x6, x7, [sp], #16
-         ldp x8, x9, [sp], #16
-         ldp x10, x11, [sp], #16
-         ldp x12, x13, [sp], #16
-         ldp x14, x15, [sp], #16
-         ldp x16, x17, [sp], #16
-         ldp x18, x29, [sp], #16
-         ldp x30, xzr, [sp], #16"
+        "ldp x0, x1, [sp, #0x100]
+         msr ELR_EL{el}, x0
+         msr SPSR_EL{el}, x1
+         ldr x0, [sp, #0xf8]
+         tbnz x1, #4, 3f
+         tbz x1, #0, 1f
+         ubfx x2, x1, #2, #2
+         cmp x2, #{el}
+         b.eq 2f
+         .if {el} == 2
+         msr SP_EL1, x0
+         .elseif {el} == 3
+         cmp x2, #0x1
+         b.ne 4f
+         msr SP_EL1, x0
+         b 3f
+         4:
+         msr SP_EL2, x0
+         .endif
+         b 3f
+         1:
+         msr SP_EL0, x0
+         b 3f
+         2:
+         ldp x2, x3, [sp, #0xe8]
+         stp x2, x3, [x0, #-16]
+         ldp x0, x1, [sp, #0x00]
+         ldp x2, x3, [sp, #0x10]
+         ldp x4, x5, [sp, #0x20]
+         ldp x6, x7, [sp, #0x30]
+         ldp x8, x9, [sp, #0x40]
+         ldp x10, x11, [sp, #0x50]
+         ldp x12, x13, [sp, #0x60]
+         ldp x14, x15, [sp, #0x70]
+         ldp x16, x17, [sp, #0x80]
+         ldp x18, x19, [sp, #0x90]
+         ldp x20, x21, [sp, #0xa0]
+         ldp x22, x23, [sp, #0xb0]
+         ldp x24, x25, [sp, #0xc0]
+         ldp x26, x27, [sp, #0xd0]
+         ldr x28, [sp, #0xe0]
+         ldr x29, [sp, #0xf8]
+         sub sp, x29, #16
+         ldp x29, x30, [sp], #16
+         eret
+         3:
+         ldp x0, x1, [sp, #0x00]
+         ldp x2, x3, [sp, #0x10]
+         ldp x4, x5, [sp, #0x20]
+         ldp x6, x7, [sp, #0x30]
+         ldp x8, x9, [sp, #0x40]
+         ldp x10, x11, [sp, #0x50]
+         ldp x12, x13, [sp, #0x60]
+         ldp x14, x15, [sp, #0x70]
+         ldp x16, x17, [sp, #0x80]
+         ldp x18, x19, [sp, #0x90]
+         ldp x20, x21, [sp, #0xa0]
+         ldp x22, x23, [sp, #0xb0]
+         ldp x24, x25, [sp, #0xc0]
+         ldp x26, x27, [sp, #0xd0]
+         ldp x28, x29, [sp, #0xe0]
+         ldr x30, [sp, #0xf0]
+         add sp, sp, #{frame_size}
+         eret"
+    };
+}
+
+macro_rules! save_fp_regs {
+    () => {
+        "stp q0, q1, [sp, #0x020]
+         stp q2, q3, [sp, #0x040]
+         stp q4, q5, [sp, #0x060]
+         stp q6, q7, [sp, #0x080]
+         stp q8, q9, [sp, #0x0a0]
+         stp q10, q11, [sp, #0x0c0]
+         stp q12, q13, [sp, #0x0e0]
+         stp q14, q15, [sp, #0x100]
+         stp q16, q17, [sp, #0x120]
+         stp q18, q19, [sp, #0x140]
+         stp q20, q21, [sp, #0x160]
+         stp q22, q23, [sp, #0x180]
+         stp q24, q25, [sp, #0x1a0]
+         stp q26, q27, [sp, #0x1c0]
+         stp q28, q29, [sp, #0x1e0]
+         stp q30, q31, [sp, #0x200]
+         mrs x11, FPCR
+         mrs x12, FPSR
+         stp x11, x12, [sp, #0x000]"
+    };
+}
+
+macro_rules! restore_fp_regs {
+    () => {
+        "ldp x11, x12, [sp, #0x000]
+         msr FPCR, x11
+         msr FPSR, x12
+         ldp q0, q1, [sp, #0x020]
+         ldp q2, q3, [sp, #0x040]
+         ldp q4, q5, [sp, #0x060]
+         ldp q6, q7, [sp, #0x080]
+         ldp q8, q9, [sp, #0x0a0]
+         ldp q10, q11, [sp, #0x0c0]
+         ldp q12, q13, [sp, #0x0e0]
+         ldp q14, q15, [sp, #0x100]
+         ldp q16, q17, [sp, #0x120]
+         ldp q18, q19, [sp, #0x140]
+         ldp q20, q21, [sp, #0x160]
+         ldp q22, q23, [sp, #0x180]
+         ldp q24, q25, [sp, #0x1a0]
+         ldp q26, q27, [sp, #0x1c0]
+         ldp q28, q29, [sp, #0x1e0]
+         ldp q30, q31, [sp, #0x200]"
     };
 }
 
 macro_rules! excp_vector {
-    ($excp_sym:expr) => {
+    ($excps:ident, $group:ty, $excp:ident, $sync:literal, $excp_stack:literal) => {
         cfg_naked_asm!(
             {
-                save_regs!(),
-
+                ".if {excp_stack} == 1",        // Switch to the exception stack, unless already on it
+                // SP_EL0 is used as scratch register, the interrupted context runs on SP_ELx. A live SP_EL0
+                // (EL0 task, code on SP_EL0) belongs to an outer exception, which restores it from its frame.
+                "msr SP_EL0, x0",
+                "mrs x0, TPIDR_EL{el}",
+                "str x1, [x0, #{ctx_scratch}]",
+                "mov x1, sp",                   // Interrupted stack pointer
+                "str x1, [x0, #{ctx_sp}]",
+                "ldr x1, [x0, #{ctx_stack}]",
+                "cmp sp, x1",
+                "b.hs 8f",
+                "ldr x1, [x0, #{ctx_limit}]",
+                "cmp sp, x1",
+                "b.lo 8f",
+                "add x1, x1, #{stack_need}",    // Nested exception, switch to the emergency stack if the exception stack is exhausted
+                "cmp sp, x1",
+                "b.hs 6f",
+                "ldr x1, [x0, #{ctx_emergency_stack}]",
+                "b 9f",
+                "8:",
+                "ldr x1, [x0, #{ctx_emergency_stack}]", // Nested exception on the emergency stack stays on it
+                "cmp sp, x1",
+                "b.hs 7f",
+                "ldr x1, [x0, #{ctx_emergency_limit}]",
+                "cmp sp, x1",
+                "b.hs 6f",
+                "7:",
+                "ldr x1, [x0, #{ctx_stack}]",
+                "9:",
+                "mov sp, x1",
+                "6:",
+                "ldr x1, [x0, #{ctx_scratch}]",
+                "mrs x0, SP_EL0",
+                ".endif",
+
+                save_regs!(),                   // Save interrupted context
+                save_sys_regs!(),
+
+                ".if {excp_stack} == 1",        // Report the interrupted stack pointer
+                "mrs x9, TPIDR_EL{el}",
+                "ldr x10, [x9, #{ctx_sp}]",
+                "str x10, [sp, #0xf8]",
+                ".endif",
+
+                ".if {fp_context} == 1",        // Eager: always save FP/SIMD registers
+                "sub sp, sp, #{fp_frame_size}",
+                save_fp_regs!(),
+                ".endif",
+
+                // Lazy: the handler runs with FP access disabled. If it was enabled, the interrupted context
+                // owns the FP/SIMD registers, they are saved into its FP frame on the first FP access trap.
+                ".if {fp_context} == 2",
+                "sub sp, sp, #{fp_frame_size}",
+                "mrs x11, TPIDR_EL{el}",
+                "ldr x12, [x11, #{ctx_fp_owner}]",
+                "str x12, [sp, #{fp_prev_owner}]",
+                ".if {el} == 1",
+                "mrs x9, CPACR_EL1",
+                "tbz x9, #20, 1f",
+                "bic x10, x9, #(0x3 << 20)",
+                "msr CPACR_EL1, x10",
+                ".else",
+                "mrs x9, CPTR_EL{el}",
+                "tbnz x9, #10, 1f",
+                "orr x10, x9, #(0x1 << 10)",
+                "msr CPTR_EL{el}, x10",
+                ".endif",
+                "isb",
+                "mov x10, sp",
+                "str x10, [x11, #{ctx_fp_owner}]",
+                "1:",
+                "stp x9, xzr, [sp, #0x010]",    // Save FP trap state, FP/SIMD registers not saved yet
+
+                ".if {sync} == 1",              // FP access trap: save the registers for their owner and enable FP access, without calling the handler
+                "mrs x10, ESR_EL{el}",
+                "lsr x10, x10, #26",
+                "cmp x10, #0x07",
+                "b.ne 3f",
+                ".if {el} == 1",
+                "orr x9, x9, #(0x3 << 20)",
+                "msr CPACR_EL1, x9",
+                ".else",
+                "bic x9, x9, #(0x1 << 10)",
+                "msr CPTR_EL{el}, x9",
+                ".endif",
+                "isb",
+                "str x9, [sp, #0x010]",
+                "cbz x12, 4f",                  // No owner, or its registers are already saved
+                "ldr x10, [x12, #0x018]",
+                "cbnz x10, 4f",
+                "mov x13, sp",                  // Save into the owner's FP frame, exceptions are masked
+                "mov sp, x12",
+                save_fp_regs!(),
+                "mov x10, #1",
+                "str x10, [sp, #0x018]",
+                "mov sp, x13",
+                "b 4f",
+                "3:",
+                ".endif",
+                ".endif",
+
+                ".if {fp_context} == 0",
                 "mov x0, sp",
+                ".else",
+                "add x0, sp, #{fp_frame_size}",
+                ".endif",
                 "bl {excp}",
-
-                restore_regs!(),
-
-                "eret",
+                "4:",
+
+                ".if {fp_context} == 1",
+                restore_fp_regs!(),
+                "add sp, sp, #{fp_frame_size}",
+                ".endif",
+
+                ".if {fp_context} == 2",
+                "ldr x11, [sp, #{fp_prev_owner}]", // Restore the FP owner and trap state, then FP/SIMD registers if saved
+                "mrs x12, TPIDR_EL{el}",
+                "str x11, [x12, #{ctx_fp_owner}]",
+                "ldp x9, x10, [sp, #0x010]",
+                ".if {el} == 1",
+                "msr CPACR_EL1, x9",
+                ".else",
+                "msr CPTR_EL{el}, x9",
+                ".endif",
+                "isb",
+                "cbz x10, 5f",
+                restore_fp_regs!(),
+                "5:",
+                "add sp, sp, #{fp_frame_size}",
+                ".endif",
+
+                restore_regs!(),                // Restore (modified) context and return
             },
-                excp = sym $excp_sym,
+                excp = sym <$excps as Exceptions<$group>>::$excp,
+                el = const EL,
+                sync = const $sync,
+                excp_stack = const $excp_stack,
+                fp_context = const <$excps as Exceptions<$group>>::FP_CONTEXT as u8,
+                frame_size = const size_of::<ExceptionFrame>(),
+                fp_frame_size = const size_of::<FpFrame>(),
+                stack_need = const size_of::<ExceptionFrame>() + size_of::<FpFrame>(),
+                ctx_scratch = const offset_of!(ExceptionStackCtx, scratch),
+                ctx_sp = const offset_of!(ExceptionStackCtx, sp),
+                ctx_stack = const offset_of!(ExceptionStackCtx, stack),
+                ctx_limit = const offset_of!(ExceptionStackCtx, limit),
+                ctx_emergency_stack = const offset_of!(ExceptionStackCtx, emergency_stack),
+                ctx_emergency_limit = const offset_of!(ExceptionStackCtx, emergency_limit),
+                ctx_fp_owner = const offset_of!(ExceptionStackCtx, fp_owner),
+                fp_prev_owner = const offset_of!(FpFrame, prev_owner),
         )
     };
 }
 
+// Synchronous exceptions and SErrors taken on SP_ELx switch to the per-core exception stack. Exceptions
+// from code running on SP_EL0 or a lower EL are already taken on SP_ELx, which is a separate stack.
 impl<T> ExceptionVectors for T
 where
     T: Exceptions<ELx_SP_EL0>
@@ -218,82 +600,82 @@ where
         + Exceptions<ELy_AARCH32>,
 {
     #[unsafe(naked)]
-    unsafe extern "C" fn sync_excp_elx_sp_el0() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_EL0>>::sync_excp)
+    unsafe extern "C" fn sync_excp_elx_sp_el0<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_EL0, sync_excp, 1, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn irq_elx_sp_el0() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_EL0>>::irq)
+    unsafe extern "C" fn irq_elx_sp_el0<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_EL0, irq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn fiq_elx_sp_el0() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_EL0>>::fiq)
+    unsafe extern "C" fn fiq_elx_sp_el0<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_EL0, fiq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn serror_elx_sp_el0() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_EL0>>::serror)
+    unsafe extern "C" fn serror_elx_sp_el0<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_EL0, serror, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn sync_excp_elx_sp_elx() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_ELx>>::sync_excp)
+    unsafe extern "C" fn sync_excp_elx_sp_elx<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_ELx, sync_excp, 1, 1)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn irq_elx_sp_elx() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_ELx>>::irq)
+    unsafe extern "C" fn irq_elx_sp_elx<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_ELx, irq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn fiq_elx_sp_elx() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_ELx>>::fiq)
+    unsafe extern "C" fn fiq_elx_sp_elx<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_ELx, fiq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn serror_elx_sp_elx() -> ! {
-        excp_vector!(<T as Exceptions<ELx_SP_ELx>>::serror)
+    unsafe extern "C" fn serror_elx_sp_elx<const EL: u8>() -> ! {
+        excp_vector!(T, ELx_SP_ELx, serror, 0, 1)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn sync_excp_ely_aarch64() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH64>>::sync_excp)
+    unsafe extern "C" fn sync_excp_ely_aarch64<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH64, sync_excp, 1, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn irq_ely_aarch64() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH64>>::irq)
+    unsafe extern "C" fn irq_ely_aarch64<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH64, irq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn fiq_ely_aarch64() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH64>>::fiq)
+    unsafe extern "C" fn fiq_ely_aarch64<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH64, fiq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn serror_ely_aarch64() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH64>>::serror)
+    unsafe extern "C" fn serror_ely_aarch64<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH64, serror, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn sync_excp_ely_aarch32() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH32>>::sync_excp)
+    unsafe extern "C" fn sync_excp_ely_aarch32<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH32, sync_excp, 1, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn irq_ely_aarch32() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH32>>::irq)
+    unsafe extern "C" fn irq_ely_aarch32<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH32, irq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn fiq_ely_aarch32() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH32>>::fiq)
+    unsafe extern "C" fn fiq_ely_aarch32<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH32, fiq, 0, 0)
     }
 
     #[unsafe(naked)]
-    unsafe extern "C" fn serror_ely_aarch32() -> ! {
-        excp_vector!(<T as Exceptions<ELy_AARCH32>>::serror)
+    unsafe extern "C" fn serror_ely_aarch32<const EL: u8>() -> ! {
+        excp_vector!(T, ELy_AARCH32, serror, 0, 0)
     }
 }
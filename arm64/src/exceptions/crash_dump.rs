@@ -0,0 +1,206 @@
+use core::{
+    arch::asm,
+    fmt::{self, Write},
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    backtrace::Backtrace,
+    psci::Psci,
+    smccc::SmcccCall32,
+    stack::{stack_guard_range, stack_usage},
+    start::cpu_idx,
+    sys_regs::CURRENT_EL,
+};
+
+use super::*;
+
+pub trait CrashDump {
+    fn write_str(s: &str);
+
+    fn action(_frame: &mut ExceptionFrame) -> ! {
+        halt()
+    }
+}
+
+pub trait VectorGroup {
+    const NAME: &'static str;
+}
+
+impl VectorGroup for ELx_SP_EL0 {
+    const NAME: &'static str = "current EL, SP_EL0";
+}
+
+impl VectorGroup for ELx_SP_ELx {
+    const NAME: &'static str = "current EL, SP_ELx";
+}
+
+impl VectorGroup for ELy_AARCH64 {
+    const NAME: &'static str = "lower EL, AArch64";
+}
+
+impl VectorGroup for ELy_AARCH32 {
+    const NAME: &'static str = "lower EL, AArch32";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionKind {
+    Sync,
+    Irq,
+    Fiq,
+    SError,
+}
+
+pub struct CrashDumpExceptions<D> {
+    _phantom: PhantomData<D>,
+}
+
+impl<D: CrashDump, G: VectorGroup> Exceptions<G> for CrashDumpExceptions<D> {
+    fn sync_excp(frame: &mut ExceptionFrame) {
+        crash::<D, G>(ExceptionKind::Sync, frame)
+    }
+
+    fn irq(frame: &mut ExceptionFrame) {
+        crash::<D, G>(ExceptionKind::Irq, frame)
+    }
+
+    fn fiq(frame: &mut ExceptionFrame) {
+        crash::<D, G>(ExceptionKind::Fiq, frame)
+    }
+
+    fn serror(frame: &mut ExceptionFrame) {
+        crash::<D, G>(ExceptionKind::SError, frame)
+    }
+}
+
+const MAX_FRAMES: usize = 32;
+
+// Owner of the crash report output, cores crashing concurrently wait here forever
+static CRASH_OWNER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+// SGI sent by halt to all other cores (GICv3), their IRQ handler calls halt_core when acknowledging it
+pub const HALT_SGI: u64 = 15;
+
+pub fn crash<D: CrashDump, G: VectorGroup>(kind: ExceptionKind, frame: &mut ExceptionFrame) -> ! {
+    let cpu_idx = cpu_idx();
+
+    while let Err(owner) =
+        CRASH_OWNER.compare_exchange(usize::MAX, cpu_idx, Ordering::Acquire, Ordering::Relaxed)
+    {
+        // Nested exception while dumping on this core, print anyway
+        if owner == cpu_idx {
+            break;
+        }
+
+        unsafe { asm!("wfe") };
+    }
+
+    let _ = write_report(&mut Sink::<D>(PhantomData), kind, G::NAME, cpu_idx, frame);
+
+    D::action(frame)
+}
+
+pub fn write_report(
+    w: &mut impl Write,
+    kind: ExceptionKind,
+    origin: &str,
+    cpu_idx: usize,
+    frame: &ExceptionFrame,
+) -> fmt::Result {
+    let current_el = CURRENT_EL.read().EL().value();
+
+    writeln!(w)?;
+    writeln!(w, "*** Unhandled {:?} exception ({}) ***", kind, origin)?;
+    writeln!(w, "cpu: {}  EL: {}", cpu_idx, current_el)?;
+    if kind == ExceptionKind::Sync || kind == ExceptionKind::SError {
+        writeln!(w, "syndrome: {:?}", frame.syndrome())?;
+    }
+    writeln!(w, "esr:  {:#018x}  far:  {:#018x}", frame.esr, frame.far)?;
+    writeln!(w, "elr:  {:#018x}  spsr: {:#018x}", frame.elr, frame.spsr)?;
+    writeln!(w, "sp:   {:#018x}", frame.sp)?;
+    if stack_guard_range(cpu_idx).contains(&(frame.far as usize)) {
+        writeln!(w, "stack overflow (fault in stack guard page)")?;
+    }
+    let usage = stack_usage(cpu_idx);
+    writeln!(w, "stack: {} / {} bytes used", usage.max_used, usage.size)?;
+
+    let regs = [
+        frame.x0, frame.x1, frame.x2, frame.x3, frame.x4, frame.x5, frame.x6, frame.x7, frame.x8,
+        frame.x9, frame.x10, frame.x11, frame.x12, frame.x13, frame.x14, frame.x15, frame.x16,
+        frame.x17, frame.x18, frame.x19, frame.x20, frame.x21, frame.x22, frame.x23, frame.x24,
+        frame.x25, frame.x26, frame.x27, frame.x28, frame.fp, frame.lr,
+    ];
+
+    for (i, pair) in regs.chunks(2).enumerate() {
+        write!(w, "x{:<2}: {:#018x}", i * 2, pair[0])?;
+        if let Some(reg) = pair.get(1) {
+            write!(w, "  x{:<2}: {:#018x}", i * 2 + 1, reg)?;
+        }
+        writeln!(w)?;
+    }
+
+    writeln!(w, "backtrace:")?;
+    for (i, addr) in Backtrace::from_frame(frame).take(MAX_FRAMES).enumerate() {
+        let kind = if i == 0 { "pc" } else { "ra" };
+        writeln!(w, "  #{:<2} {} {:#018x}", i, kind, addr)?;
+    }
+
+    Ok(())
+}
+
+// Halts this core and interrupts all other cores with HALT_SGI
+pub fn halt() -> ! {
+    halt_others();
+
+    halt_core()
+}
+
+// Sends HALT_SGI to all cores but this one, if the GICv3 system register interface is in use
+pub fn halt_others() {
+    let pfr0: u64;
+    unsafe { asm!("mrs {}, ID_AA64PFR0_EL1", out(reg) pfr0) };
+    if (pfr0 >> 24) & 0xf == 0 {
+        return;
+    }
+
+    let sre: u64;
+    unsafe { asm!("mrs {}, ICC_SRE_EL1", out(reg) sre) };
+    if sre & 0x1 == 0 {
+        return;
+    }
+
+    // IRM = 1: all PEs except self
+    let sgi1r = (1 << 40) | (HALT_SGI << 24);
+    unsafe { asm!("dsb sy", "msr ICC_SGI1R_EL1, {}", "isb", in(reg) sgi1r) };
+}
+
+// Masks all exceptions and halts this core
+pub fn halt_core() -> ! {
+    loop {
+        unsafe {
+            asm!("msr DAIFSet, #0xf", "wfe");
+        }
+    }
+}
+
+pub fn reset<C: SmcccCall32>() -> ! {
+    let _ = Psci::system_reset::<C>();
+
+    halt()
+}
+
+pub fn power_off<C: SmcccCall32>() -> ! {
+    let _ = Psci::system_off::<C>();
+
+    halt()
+}
+
+struct Sink<D>(PhantomData<D>);
+
+impl<D: CrashDump> Write for Sink<D> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        D::write_str(s);
+        Ok(())
+    }
+}
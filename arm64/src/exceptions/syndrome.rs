@@ -0,0 +1,362 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionClass {
+    Unknown,
+    WfiWfe,
+    Cp15McrMrc,
+    Cp15McrrMrrc,
+    Cp14McrMrc,
+    Cp14LdcStc,
+    FpAccess,
+    Cp10Mrc,
+    PointerAuth,
+    Cp14Mrrc,
+    BranchTarget,
+    IllegalExecutionState,
+    Svc32,
+    Hvc32,
+    Smc32,
+    Svc64,
+    Hvc64,
+    Smc64,
+    MsrMrs,
+    Sve,
+    InstructionAbortLowerEl,
+    InstructionAbortSameEl,
+    PcAlignment,
+    DataAbortLowerEl,
+    DataAbortSameEl,
+    SpAlignment,
+    Fp32,
+    Fp64,
+    SError,
+    BreakpointLowerEl,
+    BreakpointSameEl,
+    SoftwareStepLowerEl,
+    SoftwareStepSameEl,
+    WatchpointLowerEl,
+    WatchpointSameEl,
+    Bkpt32,
+    VectorCatch32,
+    Brk64,
+    Other(u8),
+}
+
+impl From<u8> for ExceptionClass {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => ExceptionClass::Unknown,
+            0x01 => ExceptionClass::WfiWfe,
+            0x03 => ExceptionClass::Cp15McrMrc,
+            0x04 => ExceptionClass::Cp15McrrMrrc,
+            0x05 => ExceptionClass::Cp14McrMrc,
+            0x06 => ExceptionClass::Cp14LdcStc,
+            0x07 => ExceptionClass::FpAccess,
+            0x08 => ExceptionClass::Cp10Mrc,
+            0x09 => ExceptionClass::PointerAuth,
+            0x0c => ExceptionClass::Cp14Mrrc,
+            0x0d => ExceptionClass::BranchTarget,
+            0x0e => ExceptionClass::IllegalExecutionState,
+            0x11 => ExceptionClass::Svc32,
+            0x12 => ExceptionClass::Hvc32,
+            0x13 => ExceptionClass::Smc32,
+            0x15 => ExceptionClass::Svc64,
+            0x16 => ExceptionClass::Hvc64,
+            0x17 => ExceptionClass::Smc64,
+            0x18 => ExceptionClass::MsrMrs,
+            0x19 => ExceptionClass::Sve,
+            0x20 => ExceptionClass::InstructionAbortLowerEl,
+            0x21 => ExceptionClass::InstructionAbortSameEl,
+            0x22 => ExceptionClass::PcAlignment,
+            0x24 => ExceptionClass::DataAbortLowerEl,
+            0x25 => ExceptionClass::DataAbortSameEl,
+            0x26 => ExceptionClass::SpAlignment,
+            0x28 => ExceptionClass::Fp32,
+            0x2c => ExceptionClass::Fp64,
+            0x2f => ExceptionClass::SError,
+            0x30 => ExceptionClass::BreakpointLowerEl,
+            0x31 => ExceptionClass::BreakpointSameEl,
+            0x32 => ExceptionClass::SoftwareStepLowerEl,
+            0x33 => ExceptionClass::SoftwareStepSameEl,
+            0x34 => ExceptionClass::WatchpointLowerEl,
+            0x35 => ExceptionClass::WatchpointSameEl,
+            0x38 => ExceptionClass::Bkpt32,
+            0x3a => ExceptionClass::VectorCatch32,
+            0x3c => ExceptionClass::Brk64,
+            other => ExceptionClass::Other(other),
+        }
+    }
+}
+
+impl From<u64> for ExceptionClass {
+    fn from(esr: u64) -> Self {
+        ExceptionClass::from(((esr >> 26) & 0x3f) as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultStatus {
+    AddressSize { level: u8 },
+    Translation { level: u8 },
+    AccessFlag { level: u8 },
+    Permission { level: u8 },
+    SyncExternal,
+    SyncTagCheck,
+    SyncExternalOnWalk { level: u8 },
+    SyncParityEcc,
+    SyncParityEccOnWalk { level: u8 },
+    Alignment,
+    TlbConflict,
+    UnsupportedAtomic,
+    Other(u8),
+}
+
+impl From<u8> for FaultStatus {
+    fn from(value: u8) -> Self {
+        let level = value & 0b11;
+
+        match value & 0x3f {
+            0b000000..=0b000011 => FaultStatus::AddressSize { level },
+            0b000100..=0b000111 => FaultStatus::Translation { level },
+            0b001000..=0b001011 => FaultStatus::AccessFlag { level },
+            0b001100..=0b001111 => FaultStatus::Permission { level },
+            0b010000 => FaultStatus::SyncExternal,
+            0b010001 => FaultStatus::SyncTagCheck,
+            0b010100..=0b010111 => FaultStatus::SyncExternalOnWalk { level },
+            0b011000 => FaultStatus::SyncParityEcc,
+            0b011100..=0b011111 => FaultStatus::SyncParityEccOnWalk { level },
+            0b100001 => FaultStatus::Alignment,
+            0b110000 => FaultStatus::TlbConflict,
+            0b110001 => FaultStatus::UnsupportedAtomic,
+            other => FaultStatus::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessSize {
+    Byte,
+    Halfword,
+    Word,
+    Doubleword,
+}
+
+impl AccessSize {
+    pub fn bytes(&self) -> usize {
+        match self {
+            AccessSize::Byte => 1,
+            AccessSize::Halfword => 2,
+            AccessSize::Word => 4,
+            AccessSize::Doubleword => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataAccess {
+    pub size: AccessSize,
+    pub sign_extend: bool,
+    pub reg: u8,
+    pub sixty_four: bool,
+    pub acquire_release: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataAbort {
+    pub same_el: bool,
+    pub status: FaultStatus,
+    pub write: bool,
+    pub stage1_walk: bool,
+    pub cache_maintenance: bool,
+    pub external: bool,
+    pub far_valid: bool,
+    pub access: Option<DataAccess>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionAbort {
+    pub same_el: bool,
+    pub status: FaultStatus,
+    pub stage1_walk: bool,
+    pub external: bool,
+    pub far_valid: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SysRegAccess {
+    pub op0: u8,
+    pub op1: u8,
+    pub crn: u8,
+    pub crm: u8,
+    pub op2: u8,
+    pub reg: u8,
+    pub read: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FpException {
+    pub valid: bool,
+    pub input_denormal: bool,
+    pub inexact: bool,
+    pub underflow: bool,
+    pub overflow: bool,
+    pub divide_by_zero: bool,
+    pub invalid_operation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorState {
+    Uncontainable,
+    Unrecoverable,
+    Restartable,
+    Recoverable,
+    Corrected,
+    Other(u8),
+}
+
+impl From<u8> for ErrorState {
+    fn from(value: u8) -> Self {
+        match value {
+            0b000 => ErrorState::Uncontainable,
+            0b001 => ErrorState::Unrecoverable,
+            0b010 => ErrorState::Restartable,
+            0b011 => ErrorState::Recoverable,
+            0b110 => ErrorState::Corrected,
+            other => ErrorState::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SError {
+    pub implementation_defined: bool,
+    pub state: Option<ErrorState>,
+    pub external: bool,
+    pub iss: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syndrome {
+    Unknown,
+    WfiWfe,
+    FpAccess,
+    IllegalExecutionState,
+    Svc { imm: u16 },
+    Hvc { imm: u16 },
+    Smc { imm: u16 },
+    MsrMrs(SysRegAccess),
+    InstructionAbort(InstructionAbort),
+    PcAlignment,
+    DataAbort(DataAbort),
+    SpAlignment,
+    FpException(FpException),
+    SError(SError),
+    Breakpoint { same_el: bool },
+    SoftwareStep { same_el: bool },
+    Watchpoint { same_el: bool, write: bool },
+    Brk { imm: u16 },
+    Other { class: ExceptionClass, iss: u32 },
+}
+
+impl From<u64> for Syndrome {
+    fn from(esr: u64) -> Self {
+        let class = ExceptionClass::from(esr);
+        let iss = (esr & 0x1ff_ffff) as u32;
+        let bit = |n: u32| iss & (1 << n) != 0;
+        let bits = |lo: u32, width: u32| ((iss >> lo) & ((1 << width) - 1)) as u8;
+        let imm = (iss & 0xffff) as u16;
+
+        match class {
+            ExceptionClass::Unknown => Syndrome::Unknown,
+            ExceptionClass::WfiWfe => Syndrome::WfiWfe,
+            ExceptionClass::FpAccess => Syndrome::FpAccess,
+            ExceptionClass::IllegalExecutionState => Syndrome::IllegalExecutionState,
+            ExceptionClass::Svc32 | ExceptionClass::Svc64 => Syndrome::Svc { imm },
+            ExceptionClass::Hvc32 | ExceptionClass::Hvc64 => Syndrome::Hvc { imm },
+            ExceptionClass::Smc32 | ExceptionClass::Smc64 => Syndrome::Smc { imm },
+            ExceptionClass::MsrMrs => Syndrome::MsrMrs(SysRegAccess {
+                op0: bits(20, 2),
+                op2: bits(17, 3),
+                op1: bits(14, 3),
+                crn: bits(10, 4),
+                reg: bits(5, 5),
+                crm: bits(1, 4),
+                read: bit(0),
+            }),
+            ExceptionClass::InstructionAbortLowerEl | ExceptionClass::InstructionAbortSameEl => {
+                Syndrome::InstructionAbort(InstructionAbort {
+                    same_el: class == ExceptionClass::InstructionAbortSameEl,
+                    status: FaultStatus::from(bits(0, 6)),
+                    stage1_walk: bit(7),
+                    external: bit(9),
+                    far_valid: !bit(10),
+                })
+            }
+            ExceptionClass::PcAlignment => Syndrome::PcAlignment,
+            ExceptionClass::DataAbortLowerEl | ExceptionClass::DataAbortSameEl => {
+                let access = bit(24).then(|| DataAccess {
+                    size: match bits(22, 2) {
+                        0b00 => AccessSize::Byte,
+                        0b01 => AccessSize::Halfword,
+                        0b10 => AccessSize::Word,
+                        _ => AccessSize::Doubleword,
+                    },
+                    sign_extend: bit(21),
+                    reg: bits(16, 5),
+                    sixty_four: bit(15),
+                    acquire_release: bit(14),
+                });
+
+                Syndrome::DataAbort(DataAbort {
+                    same_el: class == ExceptionClass::DataAbortSameEl,
+                    status: FaultStatus::from(bits(0, 6)),
+                    write: bit(6),
+                    stage1_walk: bit(7),
+                    cache_maintenance: bit(8),
+                    external: bit(9),
+                    far_valid: !bit(10),
+                    access,
+                })
+            }
+            ExceptionClass::SpAlignment => Syndrome::SpAlignment,
+            ExceptionClass::Fp32 | ExceptionClass::Fp64 => Syndrome::FpException(FpException {
+                valid: bit(23),
+                input_denormal: bit(7),
+                inexact: bit(4),
+                underflow: bit(3),
+                overflow: bit(2),
+                divide_by_zero: bit(1),
+                invalid_operation: bit(0),
+            }),
+            ExceptionClass::SError => {
+                let implementation_defined = bit(24);
+                let state = (!implementation_defined && bits(0, 6) == 0b010001)
+                    .then(|| ErrorState::from(bits(10, 3)));
+
+                Syndrome::SError(SError {
+                    implementation_defined,
+                    state,
+                    external: bit(9),
+                    iss,
+                })
+            }
+            ExceptionClass::BreakpointLowerEl | ExceptionClass::BreakpointSameEl => {
+                Syndrome::Breakpoint {
+                    same_el: class == ExceptionClass::BreakpointSameEl,
+                }
+            }
+            ExceptionClass::SoftwareStepLowerEl | ExceptionClass::SoftwareStepSameEl => {
+                Syndrome::SoftwareStep {
+                    same_el: class == ExceptionClass::SoftwareStepSameEl,
+                }
+            }
+            ExceptionClass::WatchpointLowerEl | ExceptionClass::WatchpointSameEl => {
+                Syndrome::Watchpoint {
+                    same_el: class == ExceptionClass::WatchpointSameEl,
+                    write: bit(6),
+                }
+            }
+            ExceptionClass::Brk64 => Syndrome::Brk { imm },
+            class => Syndrome::Other { class, iss },
+        }
+    }
+}
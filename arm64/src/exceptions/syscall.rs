@@ -0,0 +1,136 @@
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::*;
+
+pub const MAX_SYSCALLS: usize = 64;
+
+// Returned in x0 for syscall numbers without a registered handler
+pub const SYSCALL_UNKNOWN: u64 = u64::MAX;
+
+// Args x0-x5, the return value is written to x0
+pub type SyscallHandler = fn(args: [u64; 6]) -> u64;
+
+static SYSCALLS: [AtomicUsize; MAX_SYSCALLS] = [const { AtomicUsize::new(0) }; MAX_SYSCALLS];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallError {
+    InvalidNumber(usize),
+}
+
+// Syscalls have a single result: the handler's return value is written to x0, x1-x7 are returned
+// unchanged. Results that do not fit into x0 are passed through memory the caller points to in its args.
+pub fn register_syscall(nr: usize, handler: SyscallHandler) -> Result<(), SyscallError> {
+    let slot = SYSCALLS.get(nr).ok_or(SyscallError::InvalidNumber(nr))?;
+    slot.store(handler as usize, Ordering::Release);
+    Ok(())
+}
+
+pub fn unregister_syscall(nr: usize) -> Result<(), SyscallError> {
+    let slot = SYSCALLS.get(nr).ok_or(SyscallError::InvalidNumber(nr))?;
+    slot.store(0, Ordering::Release);
+    Ok(())
+}
+
+// The syscall number is the SVC immediate, or x8 for `svc #0`
+pub fn syscall_number(frame: &ExceptionFrame) -> Option<u64> {
+    match frame.syndrome() {
+        Syndrome::Svc { imm: 0 } => Some(frame.x8),
+        Syndrome::Svc { imm } => Some(imm as u64),
+        _ => None,
+    }
+}
+
+// Calls the registered handler if the exception is an SVC, returns false for other exceptions.
+// ELR already points to the instruction after the SVC.
+pub fn dispatch_syscall(frame: &mut ExceptionFrame) -> bool {
+    let Some(nr) = syscall_number(frame) else {
+        return false;
+    };
+
+    let handler = SYSCALLS
+        .get(nr as usize)
+        .map_or(0, |slot| slot.load(Ordering::Acquire));
+
+    frame.x0 = if handler != 0 {
+        let handler: SyscallHandler = unsafe { core::mem::transmute(handler) };
+        handler([frame.x0, frame.x1, frame.x2, frame.x3, frame.x4, frame.x5])
+    } else {
+        SYSCALL_UNKNOWN
+    };
+
+    true
+}
+
+// Dispatches SVCs to the registered syscall handlers, all other exceptions are passed on to E
+pub struct SyscallExceptions<E> {
+    _phantom: PhantomData<E>,
+}
+
+impl<E: Exceptions<G>, G> Exceptions<G> for SyscallExceptions<E> {
+    const FP_CONTEXT: FpContext = E::FP_CONTEXT;
+
+    fn sync_excp(frame: &mut ExceptionFrame) {
+        if !dispatch_syscall(frame) {
+            E::sync_excp(frame)
+        }
+    }
+
+    fn irq(frame: &mut ExceptionFrame) {
+        E::irq(frame)
+    }
+
+    fn fiq(frame: &mut ExceptionFrame) {
+        E::fiq(frame)
+    }
+
+    fn serror(frame: &mut ExceptionFrame) {
+        E::serror(frame)
+    }
+}
+
+#[doc(hidden)]
+pub const fn syscall_args<const N: usize>(args: [u64; N]) -> [u64; 6] {
+    const { assert!(N <= 6, "syscalls take at most 6 args") };
+
+    let mut res = [0; 6];
+    let mut i = 0;
+    while i < N {
+        res[i] = args[i];
+        i += 1;
+    }
+    res
+}
+
+// syscall!(nr, args...) passes the number in x8 with `svc #0`, syscall!(#imm, args...) as SVC immediate.
+// Up to 6 args in x0-x5, returns x0.
+#[macro_export]
+macro_rules! syscall {
+    (@svc $imm:expr, $nr:expr $(, $arg:expr)*) => {{
+        let args = $crate::exceptions::syscall_args([$($arg as u64),*]);
+        let ret: u64;
+        unsafe {
+            core::arch::asm!(
+                "svc #{imm}",
+                imm = const $imm,
+                in("x8") $nr as u64,
+                inout("x0") args[0] => ret,
+                in("x1") args[1],
+                in("x2") args[2],
+                in("x3") args[3],
+                in("x4") args[4],
+                in("x5") args[5],
+                options(nostack),
+            )
+        };
+        ret
+    }};
+    (#$imm:literal $(, $arg:expr)* $(,)?) => {
+        $crate::syscall!(@svc $imm, 0 $(, $arg)*)
+    };
+    ($nr:expr $(, $arg:expr)* $(,)?) => {
+        $crate::syscall!(@svc 0, $nr $(, $arg)*)
+    };
+}
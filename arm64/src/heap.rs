@@ -0,0 +1,256 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    hint::spin_loop,
+    ptr::null_mut,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::sys_regs::*;
+
+unsafe extern "C" {
+    static __heap_start: u8;
+    static __heap_end: u8;
+}
+
+// Allocation granule, every free block must be able to hold a FreeBlock
+const BLOCK_ALIGN: usize = 16;
+
+// The lock uses exclusive load/stores, so the heap is only usable once the MMU and caches are enabled.
+// It masks IRQ/FIQ via DAIF, so it can only be used at EL1 and above, not from EL0 tasks.
+#[global_allocator]
+static HEAP: Heap = Heap::new();
+
+static ALLOC_ERROR_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeapStats {
+    pub size: usize,
+    pub in_use: usize,
+    pub peak: usize,
+    pub failed_allocs: usize,
+}
+
+pub fn heap_stats() -> HeapStats {
+    HEAP.lock(|free_list| HeapStats {
+        size: free_list.size,
+        in_use: free_list.in_use,
+        peak: free_list.peak,
+        failed_allocs: free_list.failed_allocs,
+    })
+}
+
+// Called by the alloc error handler when an infallible allocation runs out of memory, before it panics.
+// Fallible allocations (try_reserve, try_with_capacity) are only counted in HeapStats::failed_allocs.
+pub fn set_alloc_error_hook(hook: fn(Layout)) {
+    ALLOC_ERROR_HOOK.store(hook as usize, Ordering::Release);
+}
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    let hook = ALLOC_ERROR_HOOK.load(Ordering::Acquire);
+    if hook != 0 {
+        let hook: fn(Layout) = unsafe { core::mem::transmute(hook) };
+        hook(layout);
+    }
+
+    panic!("memory allocation of {} bytes failed", layout.size())
+}
+
+// First-fit allocator over __heap_start..__heap_end with an address ordered free list.
+// A spin lock with IRQ/FIQ masked makes it safe to use from all cores and interrupt handlers.
+pub struct Heap {
+    locked: AtomicBool,
+    free_list: UnsafeCell<FreeList>,
+}
+
+unsafe impl Sync for Heap {}
+
+impl Heap {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            free_list: UnsafeCell::new(FreeList::new()),
+        }
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut FreeList) -> R) -> R {
+        let daif = DAIF.read();
+        DAIF.write(daif.with_I(true).with_F(true));
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+
+        let free_list = unsafe { &mut *self.free_list.get() };
+        if !free_list.initialized {
+            free_list.init();
+        }
+
+        let res = f(free_list);
+
+        self.locked.store(false, Ordering::Release);
+        DAIF.write(daif);
+
+        res
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (size, align) = block_layout(layout);
+
+        self.lock(|free_list| {
+            let ptr = unsafe { free_list.alloc(size, align) };
+            if ptr.is_null() {
+                free_list.failed_allocs += 1;
+            } else {
+                free_list.in_use += size;
+                free_list.peak = free_list.peak.max(free_list.in_use);
+            }
+            ptr
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = block_layout(layout);
+
+        self.lock(|free_list| {
+            unsafe { free_list.dealloc(ptr as usize, size) };
+            free_list.in_use -= size;
+        });
+    }
+}
+
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+struct FreeList {
+    head: *mut FreeBlock,
+    initialized: bool,
+    size: usize,
+    in_use: usize,
+    peak: usize,
+    failed_allocs: usize,
+}
+
+impl FreeList {
+    const fn new() -> Self {
+        Self {
+            head: null_mut(),
+            initialized: false,
+            size: 0,
+            in_use: 0,
+            peak: 0,
+            failed_allocs: 0,
+        }
+    }
+
+    fn init(&mut self) {
+        let start = align_up(&raw const __heap_start as usize, BLOCK_ALIGN);
+        let end = &raw const __heap_end as usize & !(BLOCK_ALIGN - 1);
+
+        if end > start {
+            self.head = start as *mut FreeBlock;
+            self.size = end - start;
+            unsafe {
+                self.head.write(FreeBlock {
+                    size: self.size,
+                    next: null_mut(),
+                })
+            };
+        }
+
+        self.initialized = true;
+    }
+
+    // size and align are multiples of BLOCK_ALIGN, so split off head and tail blocks are always large enough
+    unsafe fn alloc(&mut self, size: usize, align: usize) -> *mut u8 {
+        let mut link: *mut *mut FreeBlock = &mut self.head;
+
+        unsafe {
+            while !(*link).is_null() {
+                let block = *link;
+                let start = block as usize;
+                let end = start + (*block).size;
+                let addr = align_up(start, align);
+
+                if addr
+                    .checked_add(size)
+                    .is_some_and(|alloc_end| alloc_end <= end)
+                {
+                    let mut next = (*block).next;
+
+                    let tail = addr + size;
+                    if tail < end {
+                        let tail_block = tail as *mut FreeBlock;
+                        tail_block.write(FreeBlock {
+                            size: end - tail,
+                            next,
+                        });
+                        next = tail_block;
+                    }
+
+                    if addr > start {
+                        (*block).size = addr - start;
+                        (*block).next = next;
+                    } else {
+                        *link = next;
+                    }
+
+                    return addr as *mut u8;
+                }
+
+                link = &raw mut (*block).next;
+            }
+        }
+
+        null_mut()
+    }
+
+    // Inserts the block in address order and merges it with its neighbours
+    unsafe fn dealloc(&mut self, addr: usize, size: usize) {
+        let mut prev: *mut FreeBlock = null_mut();
+        let mut next = self.head;
+
+        unsafe {
+            while !next.is_null() && (next as usize) < addr {
+                prev = next;
+                next = (*next).next;
+            }
+
+            let block = addr as *mut FreeBlock;
+            block.write(FreeBlock { size, next });
+
+            if !next.is_null() && addr + size == next as usize {
+                (*block).size += (*next).size;
+                (*block).next = (*next).next;
+            }
+
+            if prev.is_null() {
+                self.head = block;
+            } else if prev as usize + (*prev).size == addr {
+                (*prev).size += (*block).size;
+                (*prev).next = (*block).next;
+            } else {
+                (*prev).next = block;
+            }
+        }
+    }
+}
+
+fn block_layout(layout: Layout) -> (usize, usize) {
+    let size = align_up(layout.size().max(BLOCK_ALIGN), BLOCK_ALIGN);
+    let align = layout.align().max(BLOCK_ALIGN);
+    (size, align)
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
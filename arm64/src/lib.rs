@@ -1,6 +1,8 @@
 #![no_std]
 #![feature(ptr_mask)]
 #![feature(generic_const_exprs)]
+#![feature(fn_align)]
+#![cfg_attr(feature = "heap", feature(alloc_error_handler))]
 
 #[cfg(not(target_arch = "aarch64"))]
 compile_error!("Only target_arch = \"aarch64\" is supported.");
@@ -14,19 +16,31 @@ compile_error!("Only target_endian = \"little\" is supported.");
 #[cfg(not(any(feature = "cortex-a53")))]
 compile_error!("A ARMv8A impl \"{cortex-a53, }\" must be selected.");
 
+pub mod backtrace;
 pub mod cache;
 pub mod exceptions;
+#[cfg(feature = "heap")]
+pub mod heap;
 pub mod mmu;
+pub mod mpidr;
+pub mod per_cpu;
 pub mod pmu;
 pub mod psci;
 pub mod smccc;
+pub mod smp;
+pub mod spin_table;
+pub mod stack;
 pub mod start;
 pub mod stm;
+pub mod suspend;
 pub mod sys_regs;
 pub mod sys_timer;
+pub mod tls;
+pub mod user;
 
 mod asm;
 
 pub use arbitrary_int;
+pub use fdt;
 
 pub use start::*;
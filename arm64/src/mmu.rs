@@ -217,6 +217,7 @@ impl BlockAttrs {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct PageAttrs {
     mem_typ: MemoryTyp,
     shareability: Shareability,
@@ -224,6 +225,37 @@ pub struct PageAttrs {
     security: SecurityDomain,
 }
 
+impl PageAttrs {
+    pub const DEFAULT: Self = Self {
+        mem_typ: MemoryTyp::Device_nGnRnE,
+        shareability: Shareability::Non,
+        access: Access::PrivRead,
+        security: SecurityDomain::NonSecure,
+    };
+}
+
+impl PageAttrs {
+    pub const fn with_mem_type(self, mem_typ: MemoryTyp) -> Self {
+        Self { mem_typ, ..self }
+    }
+
+    pub const fn with_shareability(self, shareability: Shareability) -> Self {
+        Self {
+            shareability,
+            ..self
+        }
+    }
+
+    pub const fn with_access(self, access: Access) -> Self {
+        Self { access, ..self }
+    }
+
+    pub const fn with_security(self, security: SecurityDomain) -> Self {
+        Self { security, ..self }
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum MemoryTyp {
     Device_nGnRnE,
     Normal_NonCacheable,
@@ -233,12 +265,14 @@ pub enum MemoryTyp {
     Normal_OuterCacheable,
 }
 
+#[derive(Clone, Copy)]
 pub enum Shareability {
     Non,
     Outer,
     Inner,
 }
 
+#[derive(Clone, Copy)]
 pub enum Access {
     PrivRead,
     PrivReadWrite,
@@ -246,6 +280,7 @@ pub enum Access {
     PrivReadWriteUnprivReadWrite,
 }
 
+#[derive(Clone, Copy)]
 pub enum SecurityDomain {
     NonSecure,
     Secure,
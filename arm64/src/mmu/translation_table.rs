@@ -10,6 +10,7 @@ use arbitrary_int::*;
 use bitbybit::*;
 
 use super::*;
+use crate::{stack::stack_guard_range, start::num_cores};
 
 #[repr(align(4096), C)]
 pub struct TranslationTable<L: TranslationLevel> {
@@ -90,8 +91,36 @@ impl TranslationTable<Level3> {
         self.entries[vaddr.LEVEL3_IDX().as_usize()] =
             TranslationTableEntry::<Level3>::page(paddr, attrs)
     }
+
+    // Maps the whole 2MB region containing vaddr with 4K pages
+    pub fn map_region(&mut self, vaddr: u64, paddr: u64, attrs: PageAttrs) {
+        let vaddr = vaddr & !(REGION_SIZE - 1);
+        let paddr = paddr & !(REGION_SIZE - 1);
+
+        for i in 0..512 {
+            self.map_page(vaddr + i * PAGE_SIZE, paddr + i * PAGE_SIZE, attrs);
+        }
+    }
+
+    // Unmaps the stack guard pages inside the 2MB region containing vaddr (unity mapped)
+    pub fn unmap_stack_guards(&mut self, vaddr: u64) {
+        let region = vaddr & !(REGION_SIZE - 1)..(vaddr & !(REGION_SIZE - 1)) + REGION_SIZE;
+
+        for cpu_idx in 0..num_cores() {
+            let guard = stack_guard_range(cpu_idx);
+
+            for page in (guard.start as u64..guard.end as u64).step_by(PAGE_SIZE as usize) {
+                if region.contains(&page) {
+                    self.unmap(page);
+                }
+            }
+        }
+    }
 }
 
+const PAGE_SIZE: u64 = 0x1000;
+const REGION_SIZE: u64 = 512 * PAGE_SIZE;
+
 #[repr(align(8), C)]
 #[derive(Clone, Copy)]
 union TranslationTableEntry<LEVEL> {
@@ -0,0 +1,84 @@
+use crate::{start::num_cores, sys_regs::*};
+
+unsafe extern "C" {
+    static __cpu_aff0_count: u8;
+    static __cpu_aff1_count: u8;
+    static __cpu_aff2_count: u8;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mpidr {
+    pub aff3: u8,
+    pub aff2: u8,
+    pub aff1: u8,
+    pub aff0: u8,
+}
+
+impl Mpidr {
+    pub fn current() -> Self {
+        Self::from(MPIDR_EL1.read())
+    }
+
+    // Dense cpu index, same mapping as in start: ((aff3 * n2 + aff2) * n1 + aff1) * n0 + aff0
+    pub fn cpu_idx(&self) -> Option<usize> {
+        let [n0, n1, n2] = aff_counts();
+
+        if self.aff0 as usize >= n0 || self.aff1 as usize >= n1 || self.aff2 as usize >= n2 {
+            return None;
+        }
+
+        let cpu_idx = ((self.aff3 as usize * n2 + self.aff2 as usize) * n1 + self.aff1 as usize)
+            * n0
+            + self.aff0 as usize;
+
+        (cpu_idx < num_cores()).then_some(cpu_idx)
+    }
+
+    pub fn from_cpu_idx(cpu_idx: usize) -> Option<Self> {
+        if cpu_idx >= num_cores() {
+            return None;
+        }
+
+        let [n0, n1, n2] = aff_counts();
+
+        Some(Self {
+            aff0: (cpu_idx % n0) as u8,
+            aff1: (cpu_idx / n0 % n1) as u8,
+            aff2: (cpu_idx / n0 / n1 % n2) as u8,
+            aff3: (cpu_idx / n0 / n1 / n2) as u8,
+        })
+    }
+
+    // Affinity fields only, as used by PSCI target_cpu / target_affinity
+    pub fn raw(&self) -> u64 {
+        (self.aff3 as u64) << 32
+            | (self.aff2 as u64) << 16
+            | (self.aff1 as u64) << 8
+            | self.aff0 as u64
+    }
+}
+
+impl From<MPIDR_EL1> for Mpidr {
+    fn from(value: MPIDR_EL1) -> Self {
+        Self {
+            aff3: value.AFF3(),
+            aff2: value.AFF2(),
+            aff1: value.AFF1(),
+            aff0: value.AFF0(),
+        }
+    }
+}
+
+impl From<u64> for Mpidr {
+    fn from(value: u64) -> Self {
+        Self::from(MPIDR_EL1::new_with_raw_value(value))
+    }
+}
+
+fn aff_counts() -> [usize; 3] {
+    [
+        &raw const __cpu_aff0_count as usize,
+        &raw const __cpu_aff1_count as usize,
+        &raw const __cpu_aff2_count as usize,
+    ]
+}
@@ -0,0 +1,64 @@
+use core::cell::UnsafeCell;
+
+use crate::sys_regs::*;
+
+unsafe extern "C" {
+    static __percpu_start: u8;
+    static __percpu_end: u8;
+    static __percpu_area_start: u8;
+}
+
+#[macro_export]
+macro_rules! per_cpu {
+    ($($(#[$attrs:meta])* $vis:vis static $name:ident: $t:ty = $init:expr;)*) => {
+        $(
+            $(#[$attrs])*
+            #[unsafe(link_section = ".percpu")]
+            $vis static $name: $crate::per_cpu::PerCpu<$t> = $crate::per_cpu::PerCpu::new($init);
+        )*
+    };
+}
+
+// The static itself is only the template in .percpu, rust_init copies it into each core's per-cpu area
+pub struct PerCpu<T> {
+    value: UnsafeCell<T>,
+}
+
+// get hands out &T to the interrupted code and IRQ handlers on the same core at the same time
+unsafe impl<T: Sync> Sync for PerCpu<T> {}
+
+impl<T> PerCpu<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        unsafe { &*self.ptr(per_cpu_base()) }
+    }
+
+    pub fn get_cpu(&self, cpu_idx: usize) -> &T {
+        unsafe { &*self.ptr(per_cpu_base_of(cpu_idx)) }
+    }
+
+    fn ptr(&self, base: usize) -> *mut T {
+        let offset = self.value.get() as usize - &raw const __percpu_start as usize;
+        (base + offset) as *mut T
+    }
+}
+
+pub fn per_cpu_base() -> usize {
+    let base = match CURRENT_EL.read().EL().value() {
+        3 => TPIDR_EL3.read().TID(),
+        2 => TPIDR_EL2.read().TID(),
+        _ => TPIDR_EL1.read().TID(),
+    };
+
+    base as usize
+}
+
+pub fn per_cpu_base_of(cpu_idx: usize) -> usize {
+    let size = &raw const __percpu_end as usize - &raw const __percpu_start as usize;
+    &raw const __percpu_area_start as usize + size * cpu_idx
+}
@@ -0,0 +1,181 @@
+use core::{marker::PhantomData, time::Duration};
+
+use fdt::{Cpu, EnableMethod, Fdt};
+
+use crate::{
+    cache::*, mpidr::Mpidr, psci::*, smccc::*, spin_table::*, start::num_cores, sys_timer::SysTimer,
+};
+
+unsafe extern "C" {
+    static __cpu_online_start: u8;
+}
+
+// One cache line per core, written by the core itself in rust_entry with MMU and caches disabled
+const ONLINE_FLAG_SIZE: usize = 0x40;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum CpuOnError {
+    Psci(PsciError),
+    SpinTable(SpinTableError),
+    UnsupportedEnableMethod,
+}
+
+impl From<PsciError> for CpuOnError {
+    fn from(value: PsciError) -> Self {
+        Self::Psci(value)
+    }
+}
+
+impl From<SpinTableError> for CpuOnError {
+    fn from(value: SpinTableError) -> Self {
+        Self::SpinTable(value)
+    }
+}
+
+// Starts a core with the FDT enable-method, PSCI calls use the conduit C.
+// Cores parked by a firmware spin-table do not get arg, only cores parked by start do.
+pub fn cpu_on<C: SmcccCall64>(cpu: &Cpu, entry: u64, arg: u64) -> Result<(), CpuOnError> {
+    match cpu.enable_method {
+        EnableMethod::Psci => Psci::cpu_on_64::<C>(cpu.mpidr, entry, arg)?,
+        EnableMethod::SpinTable { release_addr: addr } => {
+            match Mpidr::from(cpu.mpidr).cpu_idx() {
+                Some(cpu_idx) if release_addr(cpu_idx) == Some(addr) => {
+                    release_secondary(cpu_idx, entry, arg)?
+                }
+                // The firmware which parked the core also provided the FDT
+                _ => unsafe { release(addr, entry) },
+            }
+        }
+        EnableMethod::Other => return Err(CpuOnError::UnsupportedEnableMethod),
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum SmpError {
+    InvalidCpu(usize),
+    CpuOn(usize, CpuOnError),
+    // PSCI reports the core as off while waiting for it
+    Off(usize),
+    Timeout(usize),
+}
+
+// Starts secondary cores one after another and waits until each reached Rust.
+// Cores are started via PSCI cpu_on, or with the enable-method of their FDT cpu node if an FDT is set.
+pub struct SmpManager<'a, C> {
+    entry: u64,
+    timeout: Duration,
+    fdt: Option<&'a Fdt<'a>>,
+    _conduit: PhantomData<C>,
+}
+
+impl<'a, C: SmcccCall64> SmpManager<'a, C> {
+    pub fn new(entry: u64) -> Self {
+        Self {
+            entry,
+            timeout: DEFAULT_TIMEOUT,
+            fdt: None,
+            _conduit: PhantomData,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn fdt(mut self, fdt: &'a Fdt<'a>) -> Self {
+        self.fdt = Some(fdt);
+        self
+    }
+
+    // Starts all cores with their arg, returns the first error (see online_cpus for the cores which came up)
+    pub fn start_cpus(&self, cpus: impl IntoIterator<Item = (usize, u64)>) -> Result<(), SmpError> {
+        let mut res = Ok(());
+        for (cpu_idx, arg) in cpus {
+            if let Err(err) = self.start_cpu(cpu_idx, arg)
+                && res.is_ok()
+            {
+                res = Err(err);
+            }
+        }
+
+        res
+    }
+
+    pub fn start_cpu(&self, cpu_idx: usize, arg: u64) -> Result<(), SmpError> {
+        let mpidr = Mpidr::from_cpu_idx(cpu_idx)
+            .filter(|_| cpu_idx != 0)
+            .ok_or(SmpError::InvalidCpu(cpu_idx))?
+            .raw();
+
+        let cpu = match self.fdt {
+            Some(fdt) => Some(
+                fdt.cpus()
+                    .find(|cpu| cpu.mpidr == mpidr)
+                    .ok_or(SmpError::InvalidCpu(cpu_idx))?,
+            ),
+            None => None,
+        };
+        let is_psci = cpu.is_none_or(|cpu| cpu.enable_method == EnableMethod::Psci);
+
+        set_online(cpu_idx, false);
+
+        match cpu {
+            Some(cpu) => cpu_on::<C>(&cpu, self.entry, arg),
+            None => Psci::cpu_on_64::<C>(mpidr, self.entry, arg).map_err(CpuOnError::from),
+        }
+        .map_err(|err| SmpError::CpuOn(cpu_idx, err))?;
+
+        let end = SysTimer::get_time_us() + self.timeout.as_micros() as u64;
+        while !is_online(cpu_idx) {
+            if SysTimer::get_time_us() >= end {
+                return Err(SmpError::Timeout(cpu_idx));
+            }
+
+            if is_psci && let Ok(AffinityInfo::Off) = Psci::affinity_info_64::<C>(mpidr, 0) {
+                // The core may have reached Rust and turned itself off since the last check
+                if is_online(cpu_idx) {
+                    break;
+                }
+                return Err(SmpError::Off(cpu_idx));
+            }
+
+            SysTimer::wait_us(100);
+        }
+
+        Ok(())
+    }
+}
+
+pub fn is_online(cpu_idx: usize) -> bool {
+    if cpu_idx >= num_cores() {
+        return false;
+    }
+
+    let ptr = online_flag(cpu_idx).cast_const();
+    DCache::op_range(CacheOp::Invalidate, ptr..=ptr);
+    unsafe { ptr.read_volatile() != 0 }
+}
+
+pub fn online_cpus() -> impl Iterator<Item = usize> {
+    (0..num_cores()).filter(|cpu_idx| is_online(*cpu_idx))
+}
+
+// Flags are only written while the core is off or by the core itself, the line is pushed out to memory
+// so a write-back from this core's cache cannot overwrite it later
+pub(crate) fn set_online(cpu_idx: usize, online: bool) {
+    let ptr = online_flag(cpu_idx);
+    unsafe { ptr.write_volatile(online as u8) };
+    DCache::op_range(
+        CacheOp::CleanInvalidate,
+        ptr.cast_const()..=ptr.cast_const(),
+    );
+}
+
+fn online_flag(cpu_idx: usize) -> *mut u8 {
+    (&raw const __cpu_online_start as usize + cpu_idx * ONLINE_FLAG_SIZE) as *mut u8
+}
@@ -0,0 +1,70 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{cache::*, dsb, sev, start::num_cores};
+
+unsafe extern "C" {
+    static __spin_table_start: u8;
+}
+
+// One cache line per core, the mailbox is polled with MMU and caches disabled (see start)
+pub(crate) const MAILBOX_SIZE: usize = 0x40;
+
+#[repr(C)]
+struct Mailbox {
+    entry: AtomicU64,
+    arg: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpinTableError {
+    InvalidCpu,
+    InvalidEntry,
+    AlreadyReleased,
+}
+
+// Mailbox of a secondary core parked by start, e.g. for the cpu-release-addr of an FDT passed on
+pub fn release_addr(cpu_idx: usize) -> Option<u64> {
+    if cpu_idx == 0 || cpu_idx >= num_cores() {
+        return None;
+    }
+
+    Some((&raw const __spin_table_start as usize + cpu_idx * MAILBOX_SIZE) as u64)
+}
+
+// Releases a secondary core parked in the spin-table (started on its own through #[entry(spin_table)]).
+// It jumps to entry (e.g. _secondary_start) with arg in x0, in the EL it was parked in with MMU and caches disabled.
+pub fn release_secondary(cpu_idx: usize, entry: u64, arg: u64) -> Result<(), SpinTableError> {
+    let addr = release_addr(cpu_idx).ok_or(SpinTableError::InvalidCpu)?;
+    if entry == 0 {
+        return Err(SpinTableError::InvalidEntry);
+    }
+
+    let mailbox = unsafe { &*(addr as *const Mailbox) };
+    if mailbox.entry.load(Ordering::Acquire) != 0 {
+        return Err(SpinTableError::AlreadyReleased);
+    }
+
+    mailbox.arg.store(arg, Ordering::Relaxed);
+    mailbox.entry.store(entry, Ordering::Release);
+    wake(mailbox as *const Mailbox);
+
+    Ok(())
+}
+
+/// Releases a core parked by the firmware in a spin-table (FDT enable-method "spin-table"),
+/// the core jumps to entry without an argument.
+///
+/// # Safety
+/// `release_addr` must be the cpu-release-addr of a parked core.
+pub unsafe fn release(release_addr: u64, entry: u64) {
+    let ptr = release_addr as *mut u64;
+    unsafe { ptr.write_volatile(entry) };
+    wake(ptr);
+}
+
+// Pushes the mailbox out to memory for the non-cacheable reads of the parked core, then wakes it
+fn wake<T>(ptr: *const T) {
+    DCache::op_range(CacheOp::Clean, ptr..=ptr);
+    dsb!("sy");
+    sev!();
+}
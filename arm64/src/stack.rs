@@ -0,0 +1,128 @@
+use core::ops::Range;
+
+use crate::{per_cpu::PerCpu, start::num_cores};
+
+pub const STACK_PATTERN: u64 = 0xfefefefedeadc0de;
+
+unsafe extern "C" {
+    static __stack_start: u8;
+    static __stack_end: u8;
+    static __stack_guard_size: u8;
+    static __exception_stack_start: u8;
+    static __exception_stack_end: u8;
+    static __emergency_stack_start: u8;
+    static __emergency_stack_end: u8;
+}
+
+// Top and limit of this core's exception and emergency stacks, sp is the interrupted stack pointer.
+// fp_owner is the FpFrame the live FP/SIMD registers are saved into on the next FP access trap (lazy FP context).
+#[repr(C)]
+pub struct ExceptionStackCtx {
+    pub scratch: u64,
+    pub sp: u64,
+    pub stack: u64,
+    pub limit: u64,
+    pub emergency_stack: u64,
+    pub emergency_limit: u64,
+    pub fp_owner: u64,
+}
+
+// Must be the first per-cpu item, the exception vectors find it via TPIDR_ELx
+#[used]
+#[unsafe(link_section = ".percpu.exception_stack_ctx")]
+static EXCEPTION_STACK_CTX: PerCpu<ExceptionStackCtx> = PerCpu::new(ExceptionStackCtx {
+    scratch: 0,
+    sp: 0,
+    stack: 0,
+    limit: 0,
+    emergency_stack: 0,
+    emergency_limit: 0,
+    fp_owner: 0,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackUsage {
+    pub size: usize,
+    pub max_used: usize,
+}
+
+impl StackUsage {
+    pub fn remaining(&self) -> usize {
+        self.size - self.max_used
+    }
+}
+
+// Same split as in rust_init, the stack of core 0 is at the top of the stack section
+fn stack_slot(cpu_idx: usize) -> Range<usize> {
+    let start = &raw const __stack_start as usize;
+    let end = &raw const __stack_end as usize;
+
+    let start = start.min(end);
+    let size = (end - start) / num_cores();
+
+    let end = end - size * cpu_idx;
+    end - size..end
+}
+
+pub fn stack_range(cpu_idx: usize) -> Range<usize> {
+    let slot = stack_slot(cpu_idx);
+    slot.start + stack_guard_size()..slot.end
+}
+
+pub fn stack_guard_range(cpu_idx: usize) -> Range<usize> {
+    let slot = stack_slot(cpu_idx);
+    slot.start..slot.start + stack_guard_size()
+}
+
+pub fn stack_guard_size() -> usize {
+    &raw const __stack_guard_size as usize
+}
+
+pub fn exception_stack_range(cpu_idx: usize) -> Range<usize> {
+    let start = &raw const __exception_stack_start as usize;
+    let end = &raw const __exception_stack_end as usize;
+
+    let size = (end - start) / num_cores();
+
+    let end = end - size * cpu_idx;
+    end - size..end
+}
+
+pub fn emergency_stack_range(cpu_idx: usize) -> Range<usize> {
+    let start = &raw const __emergency_stack_start as usize;
+    let end = &raw const __emergency_stack_end as usize;
+
+    let size = (end - start) / num_cores();
+
+    let end = end - size * cpu_idx;
+    end - size..end
+}
+
+// High-water mark, the stack grows down so the lowest overwritten pattern word marks the max usage
+pub fn stack_usage(cpu_idx: usize) -> StackUsage {
+    let range = stack_range(cpu_idx);
+
+    let mut addr = range.start;
+    while addr < range.end && unsafe { (addr as *const u64).read_volatile() } == STACK_PATTERN {
+        addr += 8;
+    }
+
+    StackUsage {
+        size: range.len(),
+        max_used: range.end - addr,
+    }
+}
+
+pub fn check_stack_usage(
+    cpu_idx: usize,
+    margin: usize,
+    on_limit: impl FnOnce(usize, StackUsage),
+) -> StackUsage {
+    let usage = stack_usage(cpu_idx);
+
+    if usage.remaining() < margin {
+        on_limit(cpu_idx, usage);
+    }
+
+    usage
+}
@@ -1,18 +1,35 @@
-use core::{arch::naked_asm, usize};
+use core::{arch::naked_asm, mem::offset_of, usize};
 
+use arbitrary_int::*;
 use cfg_asm::cfg_naked_asm;
 
 pub use entry_macro::*;
 
-use crate::{exceptions::*, sys_regs::*};
+use crate::{
+    exceptions::*,
+    mpidr::Mpidr,
+    smp::set_online,
+    spin_table::MAILBOX_SIZE,
+    stack::{ExceptionStackCtx, STACK_PATTERN},
+    sys_regs::*,
+};
 
 pub trait Entry {
+    const TARGET_EL: Option<usize> = None;
+    const FP_ENABLE: bool = false;
+    // Secondary cores started on their own through this entry park in the spin-table (see spin_table)
+    const PARK_SECONDARIES: bool = false;
+
     unsafe extern "C" fn entry(info: EntryInfo) -> !;
 }
 
 #[repr(C)]
 pub struct EntryInfo {
-    pub param: u64,
+    pub arg0: u64,
+    pub arg1: u64,
+    pub arg2: u64,
+    pub arg3: u64,
+    pub boot_el: usize,
     pub current_el: usize,
     pub cpu_idx: usize,
     pub num_cores: usize,
@@ -26,25 +43,82 @@ pub struct EntryInfo {
     x16-x17:    intra-procedure-call scratch registers
     x18:        platform register
     x19-x28:    callee-saved
-        x19:        param
+        x19:        link offset (link - run address of the start code, 0 once running at the link address)
         x20:        current el
         x21:        cpu idx
         x22:        num cores
+        x23:        boot arg x0 (fdt phys addr / PSCI context id)
+        x24:        boot el
+        x25:        boot arg x1
+        x26:        boot arg x2
+        x27:        boot arg x3
 */
 
-static mut SEC_CORE_LOCK: usize = 1;
+unsafe extern "C" {
+    static __NUM_CPU: u8;
+    static __preinit_array_start: u8;
+    static __preinit_array_end: u8;
+    static __init_array_start: u8;
+    static __init_array_end: u8;
+}
+
+// Cores without a valid cpu idx never leave start
+pub fn cpu_idx() -> usize {
+    Mpidr::current().cpu_idx().unwrap_or_default()
+}
+
+pub fn num_cores() -> usize {
+    &raw const __NUM_CPU as usize
+}
+
+// Secondary cores are released by writing a magic value, so a lock read from uninitialized RAM
+// (before .data is copied in ROM/XIP images) does not release them
+const SEC_CORE_RELEASE: usize = 0x5ec0_c0de_0e1e_a5ed;
+
+const R_AARCH64_RELATIVE: u32 = 1027;
+
+static mut SEC_CORE_LOCK: usize = 0;
 
 #[unsafe(naked)]
 pub unsafe extern "C" fn start<EntryImpl: Entry, ExcpVecs: ExceptionVectors>() -> ! {
     cfg_naked_asm!({
-        "mov x23, x0",                  // save param (fdt phys addr)
+        "mov x23, x0",                  // Save boot args (fdt phys addr / PSCI context id)
+        "mov x25, x1",
+        "mov x26, x2",
+        "mov x27, x3",
+
+        // Addresses are PC relative until .text runs at its link address and PIE relocations are applied
+        // Link offset from the PC, cores entering at the link address (PSCI cpu_on, spin-table) get 0.
+        // PIE images run where they are loaded: the literal is 0 before relocation, the run address after.
+        "1:",
+        "adr x9, 1b",
+        "ldr x10, =1b",
+        "sub x19, x10, x9",
+        "cmp x10, #0",
+        "csel x19, xzr, x19, eq",
 
         "mrs x20, CurrentEL",           // Get CurrentEL
         "ubfm x20, x20, #0x2, #0x3",
         "cbz x20, 100f",                // Hang if we are already in EL0
-
-        "mrs x21, MPIDR_EL1",           // Get cpu idx
-        "ubfm x21, x21, #0x0, 0x7",
+        "mov x24, x20",                 // Save boot EL
+
+        "mrs x9, MPIDR_EL1",            // Get cpu idx from affinity
+        "ubfx x21, x9, #32, #8",        // cpu_idx = aff3
+        "ldr x10, =__cpu_aff2_count",
+        "ubfx x11, x9, #16, #8",
+        "cmp x11, x10",
+        "b.hs 100f",                    // Ignore cores with aff2 >= aff2_count
+        "madd x21, x21, x10, x11",      // cpu_idx = cpu_idx * aff2_count + aff2
+        "ldr x10, =__cpu_aff1_count",
+        "ubfx x11, x9, #8, #8",
+        "cmp x11, x10",
+        "b.hs 100f",                    // Ignore cores with aff1 >= aff1_count
+        "madd x21, x21, x10, x11",      // cpu_idx = cpu_idx * aff1_count + aff1
+        "ldr x10, =__cpu_aff0_count",
+        "ubfx x11, x9, #0, #8",
+        "cmp x11, x10",
+        "b.hs 100f",                    // Ignore cores with aff0 >= aff0_count
+        "madd x21, x21, x10, x11",      // cpu_idx = cpu_idx * aff0_count + aff0
 
         "ldr x22, =__NUM_CPU",          // Get core count
         
@@ -56,37 +130,77 @@ pub unsafe extern "C" fn start<EntryImpl: Entry, ExcpVecs: ExceptionVectors>() -
         #[cfg(feature = "cortex-a53")]  // Init impl specific stuff
         "bl {core_a53_init}",
 
-        "cbz x21, 10f",                 // Primary core continue with Rust init
-              
-        "ldr x9, ={sec_core_lock}",     // Secondary cores wait
+        "bl {drop_el}",                 // Drop to target EL, updates current EL
+
+        "cbz x21, 10f",                 // Primary core copies the image, then continues with Rust init
+
+        "adrp x9, {sec_core_lock}",     // Secondary cores wait
+        "add x9, x9, :lo12:{sec_core_lock}",
+        "add x9, x9, x19",
+        "ldr x11, ={sec_core_release}",
         "sevl",
         "2:",
         "wfe",
         "ldr x10, [x9]",
-        "cbnz x10, 2b",
+        "cmp x10, x11",
+        "b.ne 2b",
+
+        "mov x9, #{park_secondaries}",
+        "cbz x9, 11f",
+
+        "adrp x9, __spin_table_start",  // Park in the spin-table, until released with an entry address
+        "add x9, x9, :lo12:__spin_table_start",
+        "add x9, x9, x19",
+        "mov x10, #{mailbox_size}",
+        "madd x9, x10, x21, x9",        // mailbox = spin_table_start + (mailbox_size * cpu_idx)
+        "3:",
+        "ldar x10, [x9]",
+        "cbnz x10, 4f",
+        "wfe",
+        "b 3b",
+        "4:",
+        "ldr x0, [x9, #8]",             // Jump to the entry address, with arg in x0
+        "br x10",
 
         "10:",
+        "bl {copy_image}",              // Copy .text/.rodata and .data from load to link address
+
+        "11:",
+        "adr x9, 12f",                  // Continue at the link address
+        "add x9, x9, x19",
+        "br x9",
+
+        "12:",
+        "cbnz x21, 13f",
+        "bl {relocate}",                // Primary core applies PIE relocations
+
+        "13:",
         "bl {rust_init}",               // Init Rust
-            
-        "ldr x9, ={sec_core_lock}",     // Unlock secondary cores
+
+        "cbnz x21, 14f",
+        "bl {run_constructors}",        // Primary core runs .preinit_array/.init_array, before secondary cores are released
+        "14:",
+
+        "adrp x9, {sec_core_lock}",     // Unlock secondary cores
+        "add x9, x9, :lo12:{sec_core_lock}",
+        "ldr x12, ={sec_core_release}",
         "2:",
         "ldxr x10, [x9]",
-        "mov x10, #0",
-        "stxr w11, x10, [x9]",
+        "stxr w11, x12, [x9]",
         "cbnz x11, 2b",
         "dmb sy",
         "sev",
 
-        "mov x0, x19",                  // Zero registers
-        "mov x1, x20",
-        "mov x2, x21",
-        "mov x3, x22",
-        "mov x4, #0",
-        "mov x5, #0",
-        "mov x6, #0",
-        "mov x7, #0",
+        "mov x0, x23",                  // EntryInfo arguments: boot args x0-x3, current EL, cpu idx, num cores, boot EL
+        "mov x1, x25",
+        "mov x2, x26",
+        "mov x3, x27",
+        "mov x4, x20",
+        "mov x5, x21",
+        "mov x6, x22",
+        "mov x7, x24",
 
-        "mov x8, #0",
+        "mov x8, #0",                   // Zero registers
 
         "mov x9, #0",
         "mov x10, #0",
@@ -119,14 +233,21 @@ pub unsafe extern "C" fn start<EntryImpl: Entry, ExcpVecs: ExceptionVectors>() -
         "b 100b",
     },
     sec_core_lock = sym SEC_CORE_LOCK,
-    core_init = sym core_init::<ExcpVecs>,
+    sec_core_release = const SEC_CORE_RELEASE,
+    park_secondaries = const EntryImpl::PARK_SECONDARIES as u8,
+    mailbox_size = const MAILBOX_SIZE,
+    copy_image = sym copy_image,
+    relocate = sym relocate,
+    core_init = sym core_init::<EntryImpl, ExcpVecs>,
     core_a53_init = sym core_a53_init,
+    drop_el = sym drop_el::<EntryImpl>,
     rust_init = sym rust_init,
+    run_constructors = sym run_constructors,
     rust_entry = sym rust_entry::<EntryImpl>);
 }
 
 #[unsafe(naked)]
-unsafe extern "C" fn core_init<ExcpVecs: ExceptionVectors>() {
+unsafe extern "C" fn core_init<EntryImpl: Entry, ExcpVecs: ExceptionVectors>() {
     cfg_naked_asm!({
         "msr DAIFSet, 0xF",             // Mask all exceptions
 
@@ -151,10 +272,13 @@ unsafe extern "C" fn core_init<ExcpVecs: ExceptionVectors>() {
         "ldr x9, ={spsr_el3}",          // Set SPSR_EL3 in case of eret inst is executed
         "msr SPSR_EL3, x9",
 
-        "ldr x9, ={vectors}",           // Set VBAR_EL3
+        "adrp x9, {vectors_el3}",       // Set VBAR_EL3, at the link address
+        "add x9, x9, :lo12:{vectors_el3}",
+        "add x9, x9, x19",
         "msr VBAR_EL3, x9",
 
-        "msr CPTR_EL3, xzr",            // Do not trap to EL3: accesses to CPACR, CPACR_EL1, HCPTR, CPTR_EL2, Advanced SIMD and floating-point functionality",
+        "ldr x9, ={cptr_el3}",          // Do not trap to EL3: accesses to CPACR, CPACR_EL1, HCPTR, CPTR_EL2, Advanced SIMD and floating-point functionality",
+        "msr CPTR_EL3, x9",
 
         // Init EL2
         "12:",
@@ -167,13 +291,23 @@ unsafe extern "C" fn core_init<ExcpVecs: ExceptionVectors>() {
         "ldr x9, ={spsr_el2}",          // Set SPSR_EL2 in case of eret inst is executed
         "msr SPSR_EL2, x9",
 
+        "mrs x9, MIDR_EL1",             // Set VPIDR_EL2
+        "msr VPIDR_EL2, x9",
+
         "mrs x9, MPIDR_EL1",            // Set VMPIDR_EL2
         "msr VMPIDR_EL2, x9",
 
-        "ldr x9, ={vectors}",           // Set VBAR_EL2
+        "ldr x9, ={cnthctl_el2}",       // Do not trap EL1 accesses to the physical timer and counter
+        "msr CNTHCTL_EL2, x9",
+        "msr CNTVOFF_EL2, xzr",
+
+        "adrp x9, {vectors_el2}",       // Set VBAR_EL2, at the link address
+        "add x9, x9, :lo12:{vectors_el2}",
+        "add x9, x9, x19",
         "msr VBAR_EL2, x9",
 
-        "msr CPTR_EL2, xzr",            // Do not trap to EL2: accesses to CPACR, CPACR_EL1, Advanced SIMD and floating-point functionality"
+        "ldr x9, ={cptr_el2}",          // Do not trap to EL2: accesses to CPACR, CPACR_EL1, Advanced SIMD and floating-point functionality"
+        "msr CPTR_EL2, x9",
 
         // Init EL1
         "11:",
@@ -183,10 +317,13 @@ unsafe extern "C" fn core_init<ExcpVecs: ExceptionVectors>() {
         "ldr x9, ={spsr_el1}",          // Set SPSR_EL1 in case of eret inst is executed
         "msr SPSR_EL1, x9",
 
-        "ldr x9, ={vectors}",           // Set VBAR_EL1
+        "adrp x9, {vectors_el1}",       // Set VBAR_EL1, at the link address
+        "add x9, x9, :lo12:{vectors_el1}",
+        "add x9, x9, x19",
         "msr VBAR_EL1, x9",
 
-        "msr CPACR_EL1, xzr",           // Trap SIMD, FPU
+        "ldr x9, ={cpacr_el1}",         // Trap SIMD, FPU, unless FP is enabled
+        "msr CPACR_EL1, x9",
 
         "ret",
     },
@@ -197,12 +334,19 @@ unsafe extern "C" fn core_init<ExcpVecs: ExceptionVectors>() {
 
         scr_el3 = const SCR_EL3_INIT.raw_value(),
         hcr_el2 = const HCR_EL2_INIT.raw_value(),
+        cnthctl_el2 = const CNTHCTL_EL2_INIT.raw_value(),
 
         spsr_el3 = const SPSR_EL3_INIT.raw_value(),
         spsr_el2 = const SPSR_EL2_INIT.raw_value(),
         spsr_el1 = const SPSR_EL1_INIT.raw_value(),
 
-        vectors = sym vector_table::<ExcpVecs>,
+        cptr_el3 = const CPTR_EL3_INIT.raw_value(),
+        cptr_el2 = const CPTR_EL2_INIT.raw_value(),
+        cpacr_el1 = const cpacr_el1_init(EntryImpl::FP_ENABLE).raw_value(),
+
+        vectors_el3 = sym vector_table::<ExcpVecs, 3>,
+        vectors_el2 = sym vector_table::<ExcpVecs, 2>,
+        vectors_el1 = sym vector_table::<ExcpVecs, 1>,
     )
 }
 
@@ -231,23 +375,163 @@ unsafe extern "C" fn core_a53_init() {
     },)
 }
 
+#[unsafe(naked)]
+unsafe extern "C" fn drop_el<EntryImpl: Entry>() {
+    cfg_naked_asm!({
+        "mov x9, #{target_el}",         // Get target EL
+        "cbz x9, 10f",                  // Stay in current EL, if no target EL is set
+        "cmp x9, x20",
+        "b.hs 10f",                     // Stay in current EL, if target EL >= current EL
+
+        "cmp x20, #0x3",                // Check current EL
+        "b.eq 13f",
+        "cmp x20, #0x2",
+        "b.eq 12f",
+        "b 10f",
+
+        // Drop from EL3
+        "13:",
+        "ldr x10, ={scr_el3}",          // Route exceptions to lower EL
+        "msr SCR_EL3, x10",
+
+        "cmp x9, #0x2",
+        "b.ne 11f",
+
+        "ldr x10, ={spsr_el3_el2}",     // Return to EL2, using SP_EL2
+        "msr SPSR_EL3, x10",
+        "msr ELR_EL3, x30",
+        "mov x20, x9",
+        "eret",
+
+        "11:",
+        "ldr x10, ={hcr_el2}",          // Route exceptions to EL1
+        "msr HCR_EL2, x10",
+
+        "ldr x10, ={spsr_el3_el1}",     // Return to EL1, using SP_EL1
+        "msr SPSR_EL3, x10",
+        "msr ELR_EL3, x30",
+        "mov x20, x9",
+        "eret",
+
+        // Drop from EL2
+        "12:",
+        "ldr x10, ={hcr_el2}",          // Route exceptions to EL1
+        "msr HCR_EL2, x10",
+
+        "ldr x10, ={spsr_el2_el1}",     // Return to EL1, using SP_EL1
+        "msr SPSR_EL2, x10",
+        "msr ELR_EL2, x30",
+        "mov x20, x9",
+        "eret",
+
+        "10:",
+        "ret",
+    },
+        target_el = const target_el(EntryImpl::TARGET_EL),
+
+        scr_el3 = const SCR_EL3_DROP.raw_value(),
+        hcr_el2 = const HCR_EL2_DROP.raw_value(),
+
+        spsr_el3_el2 = const SPSR_EL3_DROP_EL2.raw_value(),
+        spsr_el3_el1 = const SPSR_EL3_DROP_EL1.raw_value(),
+        spsr_el2_el1 = const SPSR_EL2_DROP_EL1.raw_value(),
+    )
+}
+
+// Runs from the load address, so only PC relative branches may be used
+#[unsafe(naked)]
+unsafe extern "C" fn copy_image() {
+    cfg_naked_asm!({
+        "ldr x9, =__text_load_start",   // Copy .text and .rodata, if loaded at a different address
+        "ldr x10, =__text_start",
+        "ldr x11, =__rodata_end",
+        "cmp x9, x10",
+        "b.eq 3f",
+        "2:",                           // loop
+        "cmp x10, x11",
+        "b.hs 4f",                      // done
+        "ldr x12, [x9], 0x8",
+        "str x12, [x10], 0x8",
+        "b 2b",
+        "4:",
+        "dsb sy",
+        "ic iallu",                     // Invalidate instruction cache
+        "dsb sy",
+        "isb",
+        "3:",                           // end
+
+        "ldr x9, =__data_load_start",   // Copy .data and .percpu, if loaded at a different address
+        "ldr x10, =__data_start",
+        "ldr x11, =__percpu_end",
+        "cmp x9, x10",
+        "b.eq 3f",
+        "2:",                           // loop
+        "cmp x10, x11",
+        "b.hs 3f",                      // done
+        "ldr x12, [x9], 0x8",
+        "str x12, [x10], 0x8",
+        "b 2b",
+        "3:",                           // end
+
+        "ret",
+    },)
+}
+
+// Applies the R_AARCH64_RELATIVE relocations of PIE images (linked with -pie -z notext),
+// the difference between run and link address is added to each relocated value
+#[unsafe(naked)]
+unsafe extern "C" fn relocate() {
+    cfg_naked_asm!({
+        "adrp x9, __text_start",        // offset = run address - link address
+        "add x9, x9, :lo12:__text_start",
+        "ldr x10, =__text_link_start",
+        "sub x9, x9, x10",
+
+        "adrp x10, __rela_dyn_start",
+        "add x10, x10, :lo12:__rela_dyn_start",
+        "adrp x11, __rela_dyn_end",
+        "add x11, x11, :lo12:__rela_dyn_end",
+
+        "2:",                           // loop
+        "cmp x10, x11",
+        "b.hs 3f",                      // done
+        "ldp x12, x13, [x10], 0x10",    // r_offset, r_info
+        "ldr x14, [x10], 0x8",          // r_addend
+        "cmp w13, #{r_aarch64_relative}",
+        "b.ne 2b",                      // Skip other relocation types
+        "add x14, x14, x9",
+        "str x14, [x12, x9]",           // *(r_offset + offset) = r_addend + offset
+        "b 2b",
+        "3:",                           // end
+
+        "ret",
+    },
+    r_aarch64_relative = const R_AARCH64_RELATIVE,
+    )
+}
+
 #[unsafe(naked)]
 unsafe extern "C" fn rust_init() {
     cfg_naked_asm!({
         // Init stack
-        "ldr x9, =__stack_start",
-        "ldr x10, =__stack_end",
+        "adrp x9, __stack_start",
+        "add x9, x9, :lo12:__stack_start",
+        "adrp x10, __stack_end",
+        "add x10, x10, :lo12:__stack_end",
 
         "cmp x9, x10",
         "csel x9, x9, x10, lo",         // if stack_start > stack_end, set stack_start = stack_end
 
-        "sub x11, x10, x9",             // stack_size = stack_end - stack_start
-        "udiv x11, x11, x22",           // stack_size = stack_size / num_cpu
+        "sub x11, x10, x9",             // slot_size = stack_end - stack_start
+        "udiv x11, x11, x22",           // slot_size = slot_size / num_cpu
 
-        "msub x10, x11, x21, x10",      // stack_end = stack_end - (stack_size * cpu_idx)
-        "sub x9, x10, x11",             // stack_start = stack_end - stack_size
+        "msub x10, x11, x21, x10",      // stack_end = stack_end - (slot_size * cpu_idx)
+        "sub x9, x10, x11",             // stack_start = stack_end - slot_size
+
+        "ldr x12, =__stack_guard_size",
+        "add x9, x9, x12",              // stack_start = stack_start + guard_size, guard page below
 
-        "ldr x12, =0xfefefefedeadc0de", // stack pattern
+        "ldr x12, ={stack_pattern}",    // stack pattern
 
         "2:",                           // loop
         "cmp x9, x10",
@@ -259,11 +543,101 @@ unsafe extern "C" fn rust_init() {
         "msr spsel, #0x1",              // Use ELx stack
         "mov sp, x10",
 
+        // Init per-cpu data, copy the .percpu template into this core's per-cpu area
+        "adrp x9, __percpu_start",
+        "add x9, x9, :lo12:__percpu_start",
+        "adrp x10, __percpu_end",
+        "add x10, x10, :lo12:__percpu_end",
+        "sub x11, x10, x9",             // percpu_size = percpu_end - percpu_start
+        "adrp x12, __percpu_area_start",
+        "add x12, x12, :lo12:__percpu_area_start",
+        "madd x12, x11, x21, x12",      // percpu_base = percpu_area_start + (percpu_size * cpu_idx)
+
+        "mov x14, x12",
+        "2:",                           // loop
+        "cmp x9, x10",
+        "b.hs 3f",                      // done
+        "ldr x15, [x9], 0x8",
+        "str x15, [x14], 0x8",
+        "b 2b",
+        "3:",                           // end
+
+        // Init exception and emergency stacks, their context is the first per-cpu item
+        "adrp x9, __exception_stack_start",
+        "add x9, x9, :lo12:__exception_stack_start",
+        "adrp x10, __exception_stack_end",
+        "add x10, x10, :lo12:__exception_stack_end",
+
+        "sub x11, x10, x9",             // stack_size = stack_end - stack_start
+        "udiv x11, x11, x22",           // stack_size = stack_size / num_cpu
+        "msub x10, x11, x21, x10",      // stack_end = stack_end - (stack_size * cpu_idx)
+        "sub x9, x10, x11",             // stack_start = stack_end - stack_size
+
+        "stp x10, x9, [x12, #{ctx_stack}]",
+
+        "adrp x9, __emergency_stack_start",
+        "add x9, x9, :lo12:__emergency_stack_start",
+        "adrp x10, __emergency_stack_end",
+        "add x10, x10, :lo12:__emergency_stack_end",
+
+        "sub x11, x10, x9",             // stack_size = stack_end - stack_start
+        "udiv x11, x11, x22",           // stack_size = stack_size / num_cpu
+        "msub x10, x11, x21, x10",      // stack_end = stack_end - (stack_size * cpu_idx)
+        "sub x9, x10, x11",             // stack_start = stack_end - stack_size
+
+        "stp x10, x9, [x12, #{ctx_emergency_stack}]",
+        "str xzr, [x12, #{ctx_sp}]",
+
+        "cmp x20, #0x3",                // Store per-cpu base in TPIDR_ELx
+        "b.ne 4f",
+        "msr TPIDR_EL3, x12",
+        "b 5f",
+        "4:",
+        "cmp x20, #0x2",
+        "b.ne 4f",
+        "msr TPIDR_EL2, x12",
+        "b 5f",
+        "4:",
+        "msr TPIDR_EL1, x12",
+        "5:",
+
+        // Init TLS, zero this core's TLS block and copy the .tdata template into it
+        "adrp x9, __tls_area_start",
+        "add x9, x9, :lo12:__tls_area_start",
+        "ldr x10, =__tls_block_size",
+        "madd x9, x10, x21, x9",        // tp = tls_area_start + (tls_block_size * cpu_idx)
+        "msr TPIDR_EL0, x9",            // Thread pointer, points to the TCB
+
+        "add x10, x9, x10",             // block_end = tp + tls_block_size
+        "mov x11, x9",
+        "2:",                           // loop
+        "cmp x11, x10",
+        "b.hs 3f",                      // done
+        "stp xzr, xzr, [x11], 0x10",
+        "b 2b",
+        "3:",                           // end
+
+        "ldr x10, =__tls_offset",
+        "add x9, x9, x10",              // tls_data = tp + tls_offset
+        "adrp x10, __tdata_start",
+        "add x10, x10, :lo12:__tdata_start",
+        "adrp x11, __tdata_end",
+        "add x11, x11, :lo12:__tdata_end",
+        "2:",                           // loop
+        "cmp x10, x11",
+        "b.hs 3f",                      // done
+        "ldrb w12, [x10], 0x1",
+        "strb w12, [x9], 0x1",
+        "b 2b",
+        "3:",                           // end
+
         "cbnz x21, 3f",                 // Secondary cores skip
 
         // Zero bss
-        "ldr x9, =__bss_start",
-        "ldr x10, =__bss_end",
+        "adrp x9, __bss_start",
+        "add x9, x9, :lo12:__bss_start",
+        "adrp x10, __bss_end",
+        "add x10, x10, :lo12:__bss_end",
         "2:",                           // Start loop
         "cmp x9, x10",
         "b.hs 3f",                      // done
@@ -272,18 +646,58 @@ unsafe extern "C" fn rust_init() {
         "3:",                           // end
 
         "ret",
-    },)
+    },
+    stack_pattern = const STACK_PATTERN,
+    ctx_sp = const offset_of!(ExceptionStackCtx, sp),
+    ctx_stack = const offset_of!(ExceptionStackCtx, stack),
+    ctx_emergency_stack = const offset_of!(ExceptionStackCtx, emergency_stack),
+    )
+}
+
+// Runs with MMU and caches disabled, like the beginning of Entry::entry
+unsafe extern "C" fn run_constructors() {
+    let arrays = [
+        (
+            &raw const __preinit_array_start,
+            &raw const __preinit_array_end,
+        ),
+        (&raw const __init_array_start, &raw const __init_array_end),
+    ];
+
+    for (start, end) in arrays {
+        let mut ptr = start as *const usize;
+        while ptr < end as *const usize {
+            // 0 and -1 are .ctors list markers
+            let f = unsafe { ptr.read() };
+            if f != 0 && f != usize::MAX {
+                let f: extern "C" fn() = unsafe { core::mem::transmute(f) };
+                f();
+            }
+
+            ptr = unsafe { ptr.add(1) };
+        }
+    }
 }
 
 unsafe extern "C" fn rust_entry<EntryImpl: Entry>(
-    param: u64,
+    arg0: u64,
+    arg1: u64,
+    arg2: u64,
+    arg3: u64,
     current_el: u64,
     cpu_idx: u64,
     num_cores: u64,
+    boot_el: u64,
 ) -> ! {
+    set_online(cpu_idx as usize, true);
+
     unsafe {
         EntryImpl::entry(EntryInfo {
-            param,
+            arg0,
+            arg1,
+            arg2,
+            arg3,
+            boot_el: boot_el as usize,
             current_el: current_el as usize,
             cpu_idx: cpu_idx as usize,
             num_cores: num_cores as usize,
@@ -291,6 +705,21 @@ unsafe extern "C" fn rust_entry<EntryImpl: Entry>(
     }
 }
 
+const fn target_el(target_el: Option<usize>) -> u64 {
+    match target_el {
+        Some(el @ 1..=2) => el as u64,
+        _ => 0,
+    }
+}
+
+const fn cpacr_el1_init(fp_enable: bool) -> CPACR_EL1 {
+    if fp_enable {
+        CPACR_EL1::DEFAULT.with_FPEN(u2::new(0b11))
+    } else {
+        CPACR_EL1::DEFAULT
+    }
+}
+
 const SCTLR_EL3_INIT: SCTLR_EL3 = SCTLR_EL3::DEFAULT;
 const SCTLR_EL2_INIT: SCTLR_EL2 = SCTLR_EL2::DEFAULT;
 const SCTLR_EL1_INIT: SCTLR_EL1 = SCTLR_EL1::DEFAULT;
@@ -299,12 +728,24 @@ const SCR_EL3_INIT: SCR_EL3 = SCR_EL3::DEFAULT
     .with_EA(true)
     .with_FIQ(true)
     .with_IRQ(true)
-    .with_NS(true);
+    .with_NS(true)
+    .with_RW(true);
+
+const SCR_EL3_DROP: SCR_EL3 = SCR_EL3::DEFAULT.with_NS(true).with_RW(true);
 
 const HCR_EL2_INIT: HCR_EL2 = HCR_EL2::DEFAULT
     .with_AMO(true)
     .with_IMO(true)
-    .with_FMO(true);
+    .with_FMO(true)
+    .with_RW(true);
+
+pub(crate) const HCR_EL2_DROP: HCR_EL2 = HCR_EL2::DEFAULT.with_RW(true);
+
+pub(crate) const CNTHCTL_EL2_INIT: CNTHCTL_EL2 =
+    CNTHCTL_EL2::DEFAULT.with_EL1PCEN(true).with_EL1PCTEN(true);
+
+const CPTR_EL3_INIT: CPTR_EL3 = CPTR_EL3::DEFAULT.with_TFP(false);
+pub(crate) const CPTR_EL2_INIT: CPTR_EL2 = CPTR_EL2::DEFAULT.with_TFP(false);
 
 const SPSR_EL3_INIT: SPSR_EL3 = SPSR_EL3::DEFAULT
     .with_D(true)
@@ -326,3 +767,24 @@ const SPSR_EL1_INIT: SPSR_EL1 = SPSR_EL1::DEFAULT
     .with_I(true)
     .with_F(true)
     .with_M(spsr_el1::M::AARCH64_EL1_SP_EL1);
+
+const SPSR_EL3_DROP_EL2: SPSR_EL3 = SPSR_EL3::DEFAULT
+    .with_D(true)
+    .with_A(true)
+    .with_I(true)
+    .with_F(true)
+    .with_M(spsr_el3::M::AARCH64_EL2_SP_EL2);
+
+const SPSR_EL3_DROP_EL1: SPSR_EL3 = SPSR_EL3::DEFAULT
+    .with_D(true)
+    .with_A(true)
+    .with_I(true)
+    .with_F(true)
+    .with_M(spsr_el3::M::AARCH64_EL1_SP_EL1);
+
+pub(crate) const SPSR_EL2_DROP_EL1: SPSR_EL2 = SPSR_EL2::DEFAULT
+    .with_D(true)
+    .with_A(true)
+    .with_I(true)
+    .with_F(true)
+    .with_M(spsr_el2::M::AARCH64_EL1_SP_EL1);
@@ -0,0 +1,320 @@
+use core::mem::offset_of;
+
+use cfg_asm::cfg_naked_asm;
+
+use crate::{
+    cache::*,
+    psci::*,
+    smccc::*,
+    start::{CNTHCTL_EL2_INIT, CPTR_EL2_INIT, HCR_EL2_DROP, SPSR_EL2_DROP_EL1},
+};
+
+// Core state lost in a powerdown state, saved by suspend_enter and restored by resume.
+// Registers of the current EL (EL1 or EL2), in pairs for ldp/stp.
+// A core warm booted at EL2 drops to EL1 if it was suspended at EL1, other EL mismatches hang.
+#[repr(C)]
+struct SuspendCtx {
+    regs: [u64; 12],     // x19-x30
+    sp: [u64; 2],        // SP, SPSel
+    daif: [u64; 2],      // DAIF, TPIDR_EL0
+    sctlr: [u64; 2],     // SCTLR, TCR
+    mair: [u64; 2],      // MAIR, TTBR0
+    ttbr1: [u64; 2],     // TTBR1 (EL1 only), VBAR
+    tpidr: [u64; 2],     // TPIDR, CPACR_EL1 / CPTR_EL2
+    timer_ctl: [u64; 2], // CNTKCTL_EL1 / CNTHCTL_EL2, HCR_EL2 (EL2 only)
+    el: [u64; 2],        // EL at suspend, FP enabled
+    fpcr: [u64; 2],      // FPCR, FPSR, if FP is enabled
+    fp_regs: [u64; 8],   // d8-d15, if FP is enabled
+}
+
+impl SuspendCtx {
+    const fn new() -> Self {
+        Self {
+            regs: [0; 12],
+            sp: [0; 2],
+            daif: [0; 2],
+            sctlr: [0; 2],
+            mair: [0; 2],
+            ttbr1: [0; 2],
+            tpidr: [0; 2],
+            timer_ctl: [0; 2],
+            el: [0; 2],
+            fpcr: [0; 2],
+            fp_regs: [0; 8],
+        }
+    }
+}
+
+struct SuspendCall {
+    power_state: u32,
+    res: Result<(), PsciError>,
+}
+
+// Suspends the core with PSCI CPU_SUSPEND. Returns when the core woke up from a standby state,
+// or resumes here with MMU and caches re-enabled, if the core was powered down.
+// The stack must be identity mapped, the context id passed to PSCI is its address.
+pub fn suspend<C: SmcccCall64>(power_state: u32) -> Result<(), PsciError> {
+    let mut ctx = SuspendCtx::new();
+    let mut call = SuspendCall {
+        power_state,
+        res: Ok(()),
+    };
+
+    unsafe { suspend_enter(&mut ctx, &mut call, call_suspend::<C>) };
+
+    call.res
+}
+
+// Runs after the context is saved, only returns if the core was not powered down
+extern "C" fn call_suspend<C: SmcccCall64>(call: &mut SuspendCall, ctx: *mut SuspendCtx) {
+    // resume reads the context with MMU and caches disabled
+    let ctx_end = unsafe { ctx.cast::<u8>().add(size_of::<SuspendCtx>() - 1) };
+    DCache::op_range(
+        CacheOp::Clean,
+        ctx.cast::<u8>().cast_const()..=ctx_end.cast_const(),
+    );
+
+    call.res = Psci::cpu_suspend_64::<C>(call.power_state, resume as *const () as u64, ctx as u64);
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn suspend_enter(
+    ctx: *mut SuspendCtx,
+    call: &mut SuspendCall,
+    f: extern "C" fn(&mut SuspendCall, *mut SuspendCtx),
+) {
+    cfg_naked_asm!({
+        "stp x19, x20, [x0, #{regs} + 0x00]",   // Save callee-saved registers
+        "stp x21, x22, [x0, #{regs} + 0x10]",
+        "stp x23, x24, [x0, #{regs} + 0x20]",
+        "stp x25, x26, [x0, #{regs} + 0x30]",
+        "stp x27, x28, [x0, #{regs} + 0x40]",
+        "stp x29, x30, [x0, #{regs} + 0x50]",
+
+        "mov x9, sp",
+        "mrs x10, SPSel",
+        "stp x9, x10, [x0, #{sp}]",
+        "mrs x9, DAIF",
+        "mrs x10, TPIDR_EL0",
+        "stp x9, x10, [x0, #{daif}]",
+
+        "mrs x12, CurrentEL",           // Check current EL
+        "ubfx x12, x12, #2, #2",
+        "cmp x12, #0x2",
+        "b.eq 12f",
+
+        // Save EL1
+        "mrs x9, SCTLR_EL1",
+        "mrs x10, TCR_EL1",
+        "stp x9, x10, [x0, #{sctlr}]",
+        "mrs x9, MAIR_EL1",
+        "mrs x10, TTBR0_EL1",
+        "stp x9, x10, [x0, #{mair}]",
+        "mrs x9, TTBR1_EL1",
+        "mrs x10, VBAR_EL1",
+        "stp x9, x10, [x0, #{ttbr1}]",
+        "mrs x9, TPIDR_EL1",
+        "mrs x10, CPACR_EL1",
+        "stp x9, x10, [x0, #{tpidr}]",
+        "mrs x9, CNTKCTL_EL1",
+        "stp x9, xzr, [x0, #{timer_ctl}]",
+
+        "ubfx x11, x10, #20, #2",       // FP enabled, if CPACR_EL1.FPEN = 0b11
+        "cmp x11, #0x3",
+        "cset x11, eq",
+        "b 2f",
+
+        // Save EL2
+        "12:",
+        "mrs x9, SCTLR_EL2",
+        "mrs x10, TCR_EL2",
+        "stp x9, x10, [x0, #{sctlr}]",
+        "mrs x9, MAIR_EL2",
+        "mrs x10, TTBR0_EL2",
+        "stp x9, x10, [x0, #{mair}]",
+        "mrs x10, VBAR_EL2",
+        "stp xzr, x10, [x0, #{ttbr1}]",
+        "mrs x9, TPIDR_EL2",
+        "mrs x10, CPTR_EL2",
+        "stp x9, x10, [x0, #{tpidr}]",
+        "mrs x9, CNTHCTL_EL2",
+        "mrs x11, HCR_EL2",
+        "stp x9, x11, [x0, #{timer_ctl}]",
+
+        "ubfx x11, x10, #10, #1",       // FP enabled, if CPTR_EL2.TFP = 0
+        "eor x11, x11, #0x1",
+
+        "2:",
+        "stp x12, x11, [x0, #{el}]",
+        "cbz x11, 3f",                  // Save FP control/status and callee-saved FP registers
+        "mrs x9, FPCR",
+        "mrs x10, FPSR",
+        "stp x9, x10, [x0, #{fpcr}]",
+        "stp d8, d9, [x0, #{fp_regs} + 0x00]",
+        "stp d10, d11, [x0, #{fp_regs} + 0x10]",
+        "stp d12, d13, [x0, #{fp_regs} + 0x20]",
+        "stp d14, d15, [x0, #{fp_regs} + 0x30]",
+        "3:",
+
+        "mov x19, x0",                  // f(call, ctx), x19 is preserved by f
+        "mov x0, x1",
+        "mov x1, x19",
+        "blr x2",
+
+        "ldr x30, [x19, #{regs} + 0x58]", // Not powered down, return to the caller
+        "ldr x19, [x19, #{regs}]",
+        "ret",
+    },
+    regs = const offset_of!(SuspendCtx, regs),
+    sp = const offset_of!(SuspendCtx, sp),
+    daif = const offset_of!(SuspendCtx, daif),
+    sctlr = const offset_of!(SuspendCtx, sctlr),
+    mair = const offset_of!(SuspendCtx, mair),
+    ttbr1 = const offset_of!(SuspendCtx, ttbr1),
+    tpidr = const offset_of!(SuspendCtx, tpidr),
+    timer_ctl = const offset_of!(SuspendCtx, timer_ctl),
+    el = const offset_of!(SuspendCtx, el),
+    fpcr = const offset_of!(SuspendCtx, fpcr),
+    fp_regs = const offset_of!(SuspendCtx, fp_regs),
+    )
+}
+
+// PSCI warm boot entry, x0 = context. Runs with MMU and caches disabled until SCTLR is restored,
+// the caches of a powered down core are invalidated by hardware on reset.
+#[unsafe(naked)]
+unsafe extern "C" fn resume() -> ! {
+    cfg_naked_asm!({
+        "mrs x9, CurrentEL",            // Check current EL against the EL at suspend
+        "ubfx x9, x9, #2, #2",
+        "ldr x10, [x0, #{el}]",
+        "cmp x9, x10",
+        "b.eq 4f",
+
+        "cmp x9, #0x2",                 // Warm booted at EL2, suspended at EL1: drop to EL1
+        "b.ne 100f",
+        "cmp x10, #0x1",
+        "b.ne 100f",
+
+        "mrs x9, MIDR_EL1",             // Set VPIDR_EL2
+        "msr VPIDR_EL2, x9",
+        "mrs x9, MPIDR_EL1",            // Set VMPIDR_EL2
+        "msr VMPIDR_EL2, x9",
+        "ldr x9, ={cnthctl_el2}",       // Do not trap EL1 accesses to the physical timer and counter
+        "msr CNTHCTL_EL2, x9",
+        "msr CNTVOFF_EL2, xzr",
+        "ldr x9, ={cptr_el2}",          // Do not trap Advanced SIMD and floating-point functionality
+        "msr CPTR_EL2, x9",
+        "ldr x9, ={hcr_el2}",           // Route exceptions to EL1
+        "msr HCR_EL2, x9",
+
+        "ldr x9, ={spsr_el2_el1}",      // Return to EL1, using SP_EL1
+        "msr SPSR_EL2, x9",
+        "adr x9, 11f",
+        "msr ELR_EL2, x9",
+        "eret",
+
+        "100:",                         // Hang, the context can not be restored at this EL
+        "wfe",
+        "b 100b",
+
+        "4:",
+        "cmp x9, #0x2",
+        "b.eq 12f",
+
+        // Restore EL1
+        "11:",
+        "ldp x9, x10, [x0, #{mair}]",
+        "msr MAIR_EL1, x9",
+        "msr TTBR0_EL1, x10",
+        "ldp x9, x10, [x0, #{ttbr1}]",
+        "msr TTBR1_EL1, x9",
+        "msr VBAR_EL1, x10",
+        "ldp x9, x10, [x0, #{tpidr}]",
+        "msr TPIDR_EL1, x9",
+        "msr CPACR_EL1, x10",
+        "ldr x9, [x0, #{timer_ctl}]",
+        "msr CNTKCTL_EL1, x9",
+        "ldp x9, x10, [x0, #{sctlr}]",
+        "msr TCR_EL1, x10",
+        "isb",
+
+        "tlbi vmalle1",                 // Invalidate stale TLB entries and instructions
+        "ic iallu",
+        "dsb nsh",
+        "isb",
+
+        "msr SCTLR_EL1, x9",            // Enable MMU and caches
+        "isb",
+        "b 2f",
+
+        // Restore EL2
+        "12:",
+        "ldp x9, x10, [x0, #{mair}]",
+        "msr MAIR_EL2, x9",
+        "msr TTBR0_EL2, x10",
+        "ldr x10, [x0, #{ttbr1} + 0x8]",
+        "msr VBAR_EL2, x10",
+        "ldp x9, x10, [x0, #{tpidr}]",
+        "msr TPIDR_EL2, x9",
+        "msr CPTR_EL2, x10",
+        "ldp x9, x10, [x0, #{timer_ctl}]",
+        "msr CNTHCTL_EL2, x9",
+        "msr HCR_EL2, x10",
+        "ldp x9, x10, [x0, #{sctlr}]",
+        "msr TCR_EL2, x10",
+        "isb",
+
+        "tlbi alle2",                   // Invalidate stale TLB entries and instructions
+        "ic iallu",
+        "dsb nsh",
+        "isb",
+
+        "msr SCTLR_EL2, x9",            // Enable MMU and caches
+        "isb",
+
+        "2:",
+        "ldr x9, [x0, #{el} + 0x8]",    // Restore FP control/status and callee-saved FP registers
+        "cbz x9, 3f",
+        "ldp x9, x10, [x0, #{fpcr}]",
+        "msr FPCR, x9",
+        "msr FPSR, x10",
+        "ldp d8, d9, [x0, #{fp_regs} + 0x00]",
+        "ldp d10, d11, [x0, #{fp_regs} + 0x10]",
+        "ldp d12, d13, [x0, #{fp_regs} + 0x20]",
+        "ldp d14, d15, [x0, #{fp_regs} + 0x30]",
+        "3:",
+
+        "ldp x9, x10, [x0, #{sp}]",     // Restore stack pointer
+        "msr SPSel, x10",
+        "mov sp, x9",
+
+        "ldp x19, x20, [x0, #{regs} + 0x00]",   // Restore callee-saved registers
+        "ldp x21, x22, [x0, #{regs} + 0x10]",
+        "ldp x23, x24, [x0, #{regs} + 0x20]",
+        "ldp x25, x26, [x0, #{regs} + 0x30]",
+        "ldp x27, x28, [x0, #{regs} + 0x40]",
+        "ldp x29, x30, [x0, #{regs} + 0x50]",
+
+        "ldp x9, x10, [x0, #{daif}]",
+        "msr TPIDR_EL0, x10",
+        "msr DAIF, x9",
+
+        "ret",                          // Return from suspend_enter
+    },
+    regs = const offset_of!(SuspendCtx, regs),
+    sp = const offset_of!(SuspendCtx, sp),
+    daif = const offset_of!(SuspendCtx, daif),
+    sctlr = const offset_of!(SuspendCtx, sctlr),
+    mair = const offset_of!(SuspendCtx, mair),
+    ttbr1 = const offset_of!(SuspendCtx, ttbr1),
+    tpidr = const offset_of!(SuspendCtx, tpidr),
+    timer_ctl = const offset_of!(SuspendCtx, timer_ctl),
+    el = const offset_of!(SuspendCtx, el),
+    fpcr = const offset_of!(SuspendCtx, fpcr),
+    fp_regs = const offset_of!(SuspendCtx, fp_regs),
+    cnthctl_el2 = const CNTHCTL_EL2_INIT.raw_value(),
+    cptr_el2 = const CPTR_EL2_INIT.raw_value(),
+    hcr_el2 = const HCR_EL2_DROP.raw_value(),
+    spsr_el2_el1 = const SPSR_EL2_DROP_EL1.raw_value(),
+    )
+}
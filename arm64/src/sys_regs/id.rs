@@ -48,3 +48,28 @@ system_register! {
         PMICNTR: u4,
     }
 }
+
+system_register! {
+    pub MPIDR_EL1(
+        "MPIDR_EL1", u64, r,
+        res1 = 1 << 31
+    ) {
+        #[bits(32..=39, r)]
+        AFF3: u8,
+
+        #[bit(30, r)]
+        U: bool,
+
+        #[bit(24, r)]
+        MT: bool,
+
+        #[bits(16..=23, r)]
+        AFF2: u8,
+
+        #[bits(8..=15, r)]
+        AFF1: u8,
+
+        #[bits(0..=7, r)]
+        AFF0: u8
+    }
+}
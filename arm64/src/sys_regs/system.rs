@@ -72,7 +72,9 @@ system_register! {
         #[bit(2, rw)]
         FIQ: bool,
         #[bit(3, rw)]
-        EA: bool
+        EA: bool,
+        #[bit(10, rw)]
+        RW: bool
     }
 }
 
@@ -85,7 +87,47 @@ system_register! {
         #[bit(4, rw)]
         IMO: bool,
         #[bit(5, rw)]
-        AMO: bool
+        AMO: bool,
+        #[bit(31, rw)]
+        RW: bool
+    }
+}
+
+system_register! {
+    pub CPTR_EL3(
+        "CPTR_EL3", u64, rw
+    ) {
+        #[bit(31, rw)]
+        TCPAC: bool,
+        #[bit(20, rw)]
+        TTA: bool,
+        #[bit(10, rw)]
+        TFP: bool
+    }
+}
+
+system_register! {
+    pub CPTR_EL2(
+        "CPTR_EL2", u64, rw,
+        res1 = (1 << 13) | (1 << 12) | (1 << 9) | (1 << 8) | 0xFF
+    ) {
+        #[bit(31, rw)]
+        TCPAC: bool,
+        #[bit(20, rw)]
+        TTA: bool,
+        #[bit(10, rw)]
+        TFP: bool
+    }
+}
+
+system_register! {
+    pub CPACR_EL1(
+        "CPACR_EL1", u64, rw
+    ) {
+        #[bit(28, rw)]
+        TTA: bool,
+        #[bits(20..=21, rw)]
+        FPEN: u2
     }
 }
 
@@ -170,6 +212,51 @@ system_register! {
     }
 }
 
+system_register! {
+    pub ESR_EL3(
+        "ESR_EL3", u64, rw
+    ) {
+        #[bits(32..=55, rw)]
+        ISS2: u24,
+        #[bits(26..=31, rw)]
+        EC: u6,
+        #[bit(25, rw)]
+        IL: bool,
+        #[bits(0..=24, rw)]
+        ISS: u25
+    }
+}
+
+system_register! {
+    pub ESR_EL2(
+        "ESR_EL2", u64, rw
+    ) {
+        #[bits(32..=55, rw)]
+        ISS2: u24,
+        #[bits(26..=31, rw)]
+        EC: u6,
+        #[bit(25, rw)]
+        IL: bool,
+        #[bits(0..=24, rw)]
+        ISS: u25
+    }
+}
+
+system_register! {
+    pub ESR_EL1(
+        "ESR_EL1", u64, rw
+    ) {
+        #[bits(32..=55, rw)]
+        ISS2: u24,
+        #[bits(26..=31, rw)]
+        EC: u6,
+        #[bit(25, rw)]
+        IL: bool,
+        #[bits(0..=24, rw)]
+        ISS: u25
+    }
+}
+
 system_register! {
     pub DAIF(
         "DAIF", u64, rw
@@ -185,3 +272,48 @@ system_register! {
         F: bool
     }
 }
+
+system_register! {
+    pub TPIDR_EL3(
+        "TPIDR_EL3", u64, rw
+    ) {
+        #[bits(0..=63, rw)]
+        TID: u64,
+    }
+}
+
+system_register! {
+    pub TPIDR_EL2(
+        "TPIDR_EL2", u64, rw
+    ) {
+        #[bits(0..=63, rw)]
+        TID: u64,
+    }
+}
+
+system_register! {
+    pub TPIDR_EL1(
+        "TPIDR_EL1", u64, rw
+    ) {
+        #[bits(0..=63, rw)]
+        TID: u64,
+    }
+}
+
+system_register! {
+    pub TPIDR_EL0(
+        "TPIDR_EL0", u64, rw
+    ) {
+        #[bits(0..=63, rw)]
+        TID: u64,
+    }
+}
+
+system_register! {
+    pub TPIDRRO_EL0(
+        "TPIDRRO_EL0", u64, rw
+    ) {
+        #[bits(0..=63, rw)]
+        TID: u64,
+    }
+}
@@ -43,6 +43,18 @@ system_register! {
     }
 }
 
+system_register! {
+    pub CNTHCTL_EL2(
+        "CNTHCTL_EL2", u64, rw
+    ) {
+        #[bit(1, rw)]
+        EL1PCEN: bool,
+
+        #[bit(0, rw)]
+        EL1PCTEN: bool,
+    }
+}
+
 system_register! {
     pub CNTFRQ_EL0(
         "CNTFRQ_EL0", u64, rw
@@ -0,0 +1,50 @@
+use core::{alloc::Layout, ptr};
+
+use crate::sys_regs::*;
+
+unsafe extern "C" {
+    static __tdata_start: u8;
+    static __tdata_end: u8;
+    static __tls_align: u8;
+    static __tls_offset: u8;
+    static __tls_block_size: u8;
+}
+
+// Each core gets a TLS block at startup, threads need their own block with this layout
+pub fn tls_block_layout() -> Layout {
+    let size = &raw const __tls_block_size as usize;
+    let align = &raw const __tls_align as usize;
+    Layout::from_size_align(size, align).unwrap()
+}
+
+/// Initializes a TLS block from the .tdata/.tbss template and returns its thread pointer.
+///
+/// # Safety
+/// `block` must be valid for writes of `tls_block_layout()`.
+pub unsafe fn init_tls_block(block: *mut u8) -> usize {
+    let layout = tls_block_layout();
+    debug_assert!((block as usize).is_multiple_of(layout.align()));
+
+    let tdata = &raw const __tdata_start;
+    let tdata_size = &raw const __tdata_end as usize - tdata as usize;
+    let tls_offset = &raw const __tls_offset as usize;
+
+    unsafe {
+        ptr::write_bytes(block, 0, layout.size());
+        ptr::copy_nonoverlapping(tdata, block.add(tls_offset), tdata_size);
+    }
+
+    block as usize
+}
+
+pub fn thread_pointer() -> usize {
+    TPIDR_EL0.read().TID() as usize
+}
+
+/// Switches the TLS block used by `#[thread_local]` statics.
+///
+/// # Safety
+/// `tp` must be the thread pointer of a TLS block initialized with `init_tls_block`, which outlives its use.
+pub unsafe fn set_thread_pointer(tp: usize) {
+    TPIDR_EL0.write(TPIDR_EL0::new_with_raw_value(tp as u64));
+}
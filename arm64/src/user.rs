@@ -0,0 +1,339 @@
+use core::{
+    marker::PhantomData,
+    mem::offset_of,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use cfg_asm::cfg_naked_asm;
+
+use crate::{exceptions::*, per_cpu, stack::ExceptionStackCtx, sys_regs::*};
+
+// AArch64 EL0 with SP_EL0, all exceptions unmasked
+const SPSR_EL0T: u64 = 0b00000;
+
+// Kernel state saved on the stack by user_run and restored by user_return, in pairs for ldp/stp
+#[repr(C)]
+#[allow(dead_code)] // Mostly accessed from asm
+struct KernelCtx {
+    regs: [u64; 12],    // x19-x30
+    frame: [u64; 2],    // ExceptionFrame of the task, DAIF
+    ttbr0: [u64; 2],    // TTBR0_EL1, FP enabled
+    tpidr: [u64; 2],    // TPIDR_EL0 (kernel TLS), address of the task's tp
+    fp_owner: [u64; 2], // Lazy FP owner (ExceptionStackCtx), unused
+    fp_regs: [u64; 8],  // d8-d15, if FP is enabled
+}
+
+const _: () = assert!(size_of::<KernelCtx>() % 16 == 0);
+
+// user_run loads the task's registers in pairs, x28/fp and elr/spsr included
+const _: () = assert!(offset_of!(ExceptionFrame, fp) == offset_of!(ExceptionFrame, x28) + 8);
+const _: () = assert!(offset_of!(ExceptionFrame, spsr) == offset_of!(ExceptionFrame, elr) + 8);
+
+per_cpu! {
+    // KernelCtx of the task running at EL0 on this core, 0 if none
+    static KERNEL_CTX: AtomicU64 = AtomicU64::new(0);
+
+    // Stack top of the task running at EL0 on this core, 0 if none
+    static USER_STACK_TOP: AtomicU64 = AtomicU64::new(0);
+}
+
+// A task running at EL0 in its own address space. Only supported at EL1, ttbr0 must also map the
+// kernel (privileged only), as vectors and stacks stay in use while the task runs.
+// FP/SIMD registers of the task are not preserved between runs.
+pub struct UserTask {
+    pub frame: ExceptionFrame,
+    pub ttbr0: u64,
+    // TPIDR_EL0 of the task, the kernel's TLS thread pointer is restored when the task exits
+    pub tp: u64,
+    // Initial sp, bounds backtraces of the task
+    pub stack_top: u64,
+}
+
+impl UserTask {
+    pub fn new(entry: u64, sp: u64, ttbr0: u64) -> Self {
+        let mut frame: ExceptionFrame = unsafe { core::mem::zeroed() };
+        frame.elr = entry;
+        frame.sp = sp;
+        frame.spsr = SPSR_EL0T;
+
+        Self {
+            frame,
+            ttbr0,
+            tp: 0,
+            stack_top: sp,
+        }
+    }
+
+    /// Runs the task until it takes a synchronous exception, frame then holds the task's state.
+    /// ELR points behind an SVC, so the task continues with the next run.
+    ///
+    /// # Safety
+    /// `ttbr0` must map the kernel and `UserExceptions` must handle the ELy_AARCH64 vectors.
+    pub unsafe fn run(&mut self) {
+        assert_eq!(
+            CURRENT_EL.read().EL().value(),
+            1,
+            "user tasks are only supported at EL1"
+        );
+
+        let ctx = KERNEL_CTX.get().as_ptr();
+        USER_STACK_TOP
+            .get()
+            .store(self.stack_top, Ordering::Relaxed);
+        unsafe { user_run(&mut self.frame, self.ttbr0, ctx, &mut self.tp) };
+        USER_STACK_TOP.get().store(0, Ordering::Relaxed);
+    }
+}
+
+// Stack of the task running at EL0 on this core, from its sp up to the stack top
+pub fn user_stack_range(sp: usize) -> Option<Range<usize>> {
+    match USER_STACK_TOP.get().load(Ordering::Relaxed) as usize {
+        0 => None,
+        top => Some(sp..top),
+    }
+}
+
+/// Starts entry at EL0 with the stack sp and the translation table ttbr0,
+/// returns when the task takes its first synchronous exception.
+///
+/// # Safety
+/// See `UserTask::run`.
+pub unsafe fn enter_user(entry: u64, sp: u64, ttbr0: u64) -> UserTask {
+    let mut task = UserTask::new(entry, sp, ttbr0);
+    unsafe { task.run() };
+    task
+}
+
+// Returns from UserTask::run with the exception frame of the task, called from the ELy_AARCH64 sync_excp
+pub fn user_exit(frame: &ExceptionFrame) -> ! {
+    let ctx = KERNEL_CTX.get().swap(0, Ordering::Relaxed);
+    assert_ne!(ctx, 0, "no user task running on this core");
+
+    unsafe { user_return(frame, ctx) }
+}
+
+// Routes synchronous exceptions from AArch64 EL0 back to UserTask::run, all other exceptions are passed on to E
+pub struct UserExceptions<E> {
+    _phantom: PhantomData<E>,
+}
+
+impl<E: Exceptions<ELy_AARCH64>> Exceptions<ELy_AARCH64> for UserExceptions<E> {
+    const FP_CONTEXT: FpContext = E::FP_CONTEXT;
+
+    fn sync_excp(frame: &mut ExceptionFrame) {
+        user_exit(frame)
+    }
+
+    fn irq(frame: &mut ExceptionFrame) {
+        with_kernel_tp(|| E::irq(frame))
+    }
+
+    fn fiq(frame: &mut ExceptionFrame) {
+        with_kernel_tp(|| E::fiq(frame))
+    }
+
+    fn serror(frame: &mut ExceptionFrame) {
+        with_kernel_tp(|| E::serror(frame))
+    }
+}
+
+// Exceptions taken from EL0 interrupt the task with its TPIDR_EL0, handlers use the kernel's TLS
+fn with_kernel_tp(f: impl FnOnce()) {
+    let ctx = KERNEL_CTX.get().load(Ordering::Relaxed) as *const KernelCtx;
+    if ctx.is_null() {
+        return f();
+    }
+
+    let tp = TPIDR_EL0.read();
+    TPIDR_EL0.write(TPIDR_EL0::new_with_raw_value(unsafe { (*ctx).tpidr[0] }));
+    f();
+    TPIDR_EL0.write(tp);
+}
+
+macro_rules! forward_exceptions {
+    ($($group:ty),*) => {
+        $(
+            impl<E: Exceptions<$group>> Exceptions<$group> for UserExceptions<E> {
+                const FP_CONTEXT: FpContext = E::FP_CONTEXT;
+
+                fn sync_excp(frame: &mut ExceptionFrame) {
+                    E::sync_excp(frame)
+                }
+
+                fn irq(frame: &mut ExceptionFrame) {
+                    E::irq(frame)
+                }
+
+                fn fiq(frame: &mut ExceptionFrame) {
+                    E::fiq(frame)
+                }
+
+                fn serror(frame: &mut ExceptionFrame) {
+                    E::serror(frame)
+                }
+            }
+        )*
+    };
+}
+
+forward_exceptions!(ELx_SP_EL0, ELx_SP_ELx, ELy_AARCH32);
+
+#[unsafe(naked)]
+unsafe extern "C" fn user_run(frame: *mut ExceptionFrame, ttbr0: u64, ctx: *mut u64, tp: *mut u64) {
+    cfg_naked_asm!({
+        "sub sp, sp, #{ctx_size}",
+        "stp x19, x20, [sp, #{regs} + 0x00]",   // Save callee-saved registers
+        "stp x21, x22, [sp, #{regs} + 0x10]",
+        "stp x23, x24, [sp, #{regs} + 0x20]",
+        "stp x25, x26, [sp, #{regs} + 0x30]",
+        "stp x27, x28, [sp, #{regs} + 0x40]",
+        "stp x29, x30, [sp, #{regs} + 0x50]",
+
+        "mrs x9, DAIF",
+        "stp x0, x9, [sp, #{frame}]",
+        "msr DAIFSet, #0xf",            // Mask exceptions, ELR/SPSR must survive until eret
+
+        "mrs x9, TTBR0_EL1",
+        "mrs x10, CPACR_EL1",
+        "ubfx x10, x10, #20, #2",       // FP enabled, if CPACR_EL1.FPEN = 0b11
+        "cmp x10, #0x3",
+        "cset x10, eq",
+        "stp x9, x10, [sp, #{ttbr0}]",
+        "cbz x10, 2f",
+        "stp d8, d9, [sp, #{fp_regs} + 0x00]",
+        "stp d10, d11, [sp, #{fp_regs} + 0x10]",
+        "stp d12, d13, [sp, #{fp_regs} + 0x20]",
+        "stp d14, d15, [sp, #{fp_regs} + 0x30]",
+        "2:",
+
+        "mrs x9, TPIDR_EL0",            // Kernel TLS thread pointer, switch to the task's
+        "stp x9, x3, [sp, #{tpidr}]",
+        "ldr x9, [x3]",
+        "msr TPIDR_EL0, x9",
+
+        "mrs x9, TPIDR_EL1",            // Exception frames of the task are dropped by user_return, keep the lazy FP owner
+        "ldr x10, [x9, #{ctx_fp_owner}]",
+        "str x10, [sp, #{fp_owner}]",
+
+        "mov x9, sp",                   // Exceptions from EL0 are taken on SP_EL1, below the kernel context
+        "str x9, [x2]",
+
+        "msr TTBR0_EL1, x1",            // Switch to the task's address space
+        "isb",
+        "tlbi vmalle1",
+        "dsb nsh",
+        "isb",
+
+        "ldp x9, x10, [x0, #{frame_elr}]", // Restore the task's context and enter EL0
+        "msr ELR_EL1, x9",
+        "msr SPSR_EL1, x10",
+        "ldr x9, [x0, #{frame_sp}]",
+        "msr SP_EL0, x9",
+
+        "ldp x2, x3, [x0, #{frame_x2}]",
+        "ldp x4, x5, [x0, #{frame_x4}]",
+        "ldp x6, x7, [x0, #{frame_x6}]",
+        "ldp x8, x9, [x0, #{frame_x8}]",
+        "ldp x10, x11, [x0, #{frame_x10}]",
+        "ldp x12, x13, [x0, #{frame_x12}]",
+        "ldp x14, x15, [x0, #{frame_x14}]",
+        "ldp x16, x17, [x0, #{frame_x16}]",
+        "ldp x18, x19, [x0, #{frame_x18}]",
+        "ldp x20, x21, [x0, #{frame_x20}]",
+        "ldp x22, x23, [x0, #{frame_x22}]",
+        "ldp x24, x25, [x0, #{frame_x24}]",
+        "ldp x26, x27, [x0, #{frame_x26}]",
+        "ldp x28, x29, [x0, #{frame_x28}]",
+        "ldr x30, [x0, #{frame_lr}]",
+        "ldp x0, x1, [x0, #{frame_x0}]",
+        "eret",
+    },
+    ctx_size = const size_of::<KernelCtx>(),
+    regs = const offset_of!(KernelCtx, regs),
+    frame = const offset_of!(KernelCtx, frame),
+    ttbr0 = const offset_of!(KernelCtx, ttbr0),
+    tpidr = const offset_of!(KernelCtx, tpidr),
+    fp_owner = const offset_of!(KernelCtx, fp_owner),
+    ctx_fp_owner = const offset_of!(ExceptionStackCtx, fp_owner),
+    fp_regs = const offset_of!(KernelCtx, fp_regs),
+    frame_x0 = const offset_of!(ExceptionFrame, x0),
+    frame_x2 = const offset_of!(ExceptionFrame, x2),
+    frame_x4 = const offset_of!(ExceptionFrame, x4),
+    frame_x6 = const offset_of!(ExceptionFrame, x6),
+    frame_x8 = const offset_of!(ExceptionFrame, x8),
+    frame_x10 = const offset_of!(ExceptionFrame, x10),
+    frame_x12 = const offset_of!(ExceptionFrame, x12),
+    frame_x14 = const offset_of!(ExceptionFrame, x14),
+    frame_x16 = const offset_of!(ExceptionFrame, x16),
+    frame_x18 = const offset_of!(ExceptionFrame, x18),
+    frame_x20 = const offset_of!(ExceptionFrame, x20),
+    frame_x22 = const offset_of!(ExceptionFrame, x22),
+    frame_x24 = const offset_of!(ExceptionFrame, x24),
+    frame_x26 = const offset_of!(ExceptionFrame, x26),
+    frame_x28 = const offset_of!(ExceptionFrame, x28),
+    frame_lr = const offset_of!(ExceptionFrame, lr),
+    frame_sp = const offset_of!(ExceptionFrame, sp),
+    frame_elr = const offset_of!(ExceptionFrame, elr),
+    )
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn user_return(frame: &ExceptionFrame, ctx: u64) -> ! {
+    cfg_naked_asm!({
+        "mov sp, x1",                   // The exception frame is below the kernel context, it is not overwritten
+
+        "ldr x2, [sp, #{frame}]",       // Copy the exception frame into the task's frame
+        "mov x3, #{frame_size}",
+        "1:",
+        "ldp x4, x5, [x0], #0x10",
+        "stp x4, x5, [x2], #0x10",
+        "subs x3, x3, #0x10",
+        "b.ne 1b",
+
+        "ldp x9, x10, [sp, #{tpidr}]",  // Save the task's thread pointer, restore the kernel's
+        "mrs x11, TPIDR_EL0",
+        "str x11, [x10]",
+        "msr TPIDR_EL0, x9",
+
+        "ldr x9, [sp, #{fp_owner}]",    // Restore the lazy FP owner
+        "mrs x10, TPIDR_EL1",
+        "str x9, [x10, #{ctx_fp_owner}]",
+
+        "ldp x9, x10, [sp, #{ttbr0}]",  // Switch back to the kernel's address space
+        "msr TTBR0_EL1, x9",
+        "isb",
+        "tlbi vmalle1",
+        "dsb nsh",
+        "isb",
+
+        "cbz x10, 2f",                  // Restore callee-saved FP registers
+        "ldp d8, d9, [sp, #{fp_regs} + 0x00]",
+        "ldp d10, d11, [sp, #{fp_regs} + 0x10]",
+        "ldp d12, d13, [sp, #{fp_regs} + 0x20]",
+        "ldp d14, d15, [sp, #{fp_regs} + 0x30]",
+        "2:",
+
+        "ldp x19, x20, [sp, #{regs} + 0x00]",   // Restore callee-saved registers
+        "ldp x21, x22, [sp, #{regs} + 0x10]",
+        "ldp x23, x24, [sp, #{regs} + 0x20]",
+        "ldp x25, x26, [sp, #{regs} + 0x30]",
+        "ldp x27, x28, [sp, #{regs} + 0x40]",
+        "ldp x29, x30, [sp, #{regs} + 0x50]",
+
+        "ldr x9, [sp, #{frame} + 0x8]",
+        "msr DAIF, x9",
+        "add sp, sp, #{ctx_size}",
+        "ret",                          // Return from user_run
+    },
+    ctx_size = const size_of::<KernelCtx>(),
+    frame_size = const size_of::<ExceptionFrame>(),
+    regs = const offset_of!(KernelCtx, regs),
+    frame = const offset_of!(KernelCtx, frame),
+    ttbr0 = const offset_of!(KernelCtx, ttbr0),
+    tpidr = const offset_of!(KernelCtx, tpidr),
+    fp_owner = const offset_of!(KernelCtx, fp_owner),
+    ctx_fp_owner = const offset_of!(ExceptionStackCtx, fp_owner),
+    fp_regs = const offset_of!(KernelCtx, fp_regs),
+    )
+}
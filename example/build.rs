@@ -1,33 +1,30 @@
-use std::{cell::LazyCell, env, fs, path::PathBuf};
+use std::{cell::LazyCell, env};
 
-use quote::quote;
+use arm64_layout::*;
 
 const IS_FEAT_QEMU: LazyCell<bool> = LazyCell::new(|| env::var("CARGO_FEATURE_QEMU").is_ok());
 const IS_FEAT_KR260: LazyCell<bool> = LazyCell::new(|| env::var("CARGO_FEATURE_KR260").is_ok());
 
-const OUT_DIR: LazyCell<PathBuf> = LazyCell::new(|| env::var("OUT_DIR").unwrap().into());
-
 pub fn main() {
-    let memory_ld = if *IS_FEAT_QEMU {
-        quote! {
-            __NUM_CPU = 4;
-            __STACK_SIZE = 0x10000;
-            __HEAP_SIZE = 0x100000;
-            __TEXT_OFFSET = 0x40000000;
-        }
+    let layout = if *IS_FEAT_QEMU {
+        MemoryLayout::new()
+            .region("DDR", 0x4000_0000, 0x4000_0000)
+            .place(Section::Text, "DDR")
+            .cpu_aff0_count(8)
     } else if *IS_FEAT_KR260 {
-        quote! {
-            __NUM_CPU = 4;
-            __STACK_SIZE = 0x10000;
-            __HEAP_SIZE = 0x100000;
-            __TEXT_OFFSET = 0x0;
-        }
+        MemoryLayout::new()
+            .region("DDR", 0x0, 0x8000_0000)
+            .region("OCM", 0xfffc_0000, 0x4_0000)
+            .place(Section::Text, "DDR")
+            .cpu_aff0_count(4)
     } else {
-        quote! {}
+        panic!("no platform feature selected");
     };
 
-    fs::write(OUT_DIR.join("memory.ld"), memory_ld.to_string()).unwrap();
-
-    println!("cargo:rustc-link-search={}", OUT_DIR.display());
-    println!("cargo:rustc-link-arg=-Tlink.ld");
+    layout
+        .num_cpu(4)
+        .stack_size(0x10000)
+        .stack_guard_size(0x1000)
+        .heap_size(0x100000)
+        .emit();
 }
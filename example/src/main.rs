@@ -2,16 +2,23 @@
 #![no_main]
 #![feature(cfg_select)]
 
+extern crate alloc;
+
+use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::panic::PanicInfo;
 
 use log::*;
 use spin::Mutex;
 
+use arm64::backtrace::*;
 use arm64::cache::*;
+use arm64::fdt::*;
+use arm64::heap::*;
 use arm64::mmu::*;
-use arm64::psci::*;
 use arm64::smccc::*;
+use arm64::smp::*;
+use arm64::stack::*;
 use arm64::*;
 
 mod excps;
@@ -28,12 +35,16 @@ use spin_ext::*;
 struct TranslationTables {
     l0: TranslationTable<Level0>,
     l1: TranslationTable<Level1>,
+    l2: TranslationTable<Level2>,
+    l3: TranslationTable<Level3>,
 }
 
 static TRANSLATION_TABLES: Mutex<RefCell<TranslationTables>> =
     Mutex::new(RefCell::new(TranslationTables {
         l0: TranslationTable::DEFAULT,
         l1: TranslationTable::DEFAULT,
+        l2: TranslationTable::DEFAULT,
+        l3: TranslationTable::DEFAULT,
     }));
 
 // Default memory attributes for virtual memory blocks
@@ -51,6 +62,12 @@ const NORMAL_ATTRS: BlockAttrs = BlockAttrs::DEFAULT
     .with_access(Access::PrivReadWrite)
     .with_security(SecurityDomain::NonSecure);
 
+const NORMAL_PAGE_ATTRS: PageAttrs = PageAttrs::DEFAULT
+    .with_mem_type(MemoryTyp::Normal_Cacheable)
+    .with_shareability(Shareability::Inner)
+    .with_access(Access::PrivReadWrite)
+    .with_security(SecurityDomain::NonSecure);
+
 pub static LOGGER: Once<Logger<'static, plat::uart::Driver>> = Once::new();
 
 #[entry(exceptions = Excps)]
@@ -94,6 +111,30 @@ fn main(info: EntryInfo) -> ! {
             }
         }
 
+        // Split the 1GB block containing the stacks into 2MB blocks and the 2MB block into 4K pages,
+        // leaving the stack guard pages unmapped (assumes all stacks are within one 2MB block)
+        {
+            let stack_addr = stack_guard_range(info.num_cores - 1).start as u64;
+            let l2_base_addr = tables.l2.base_addr();
+            let l3_base_addr = tables.l3.base_addr();
+
+            let block_1g = stack_addr & !0x3fff_ffff;
+            tables
+                .l1
+                .map_table(block_1g, l2_base_addr as u64, TableAttrs::DEFAULT);
+            for i in 0..512 {
+                let addr = block_1g + i * 0x20_0000;
+                tables.l2.map_block(addr, addr, NORMAL_ATTRS);
+            }
+
+            let block_2m = stack_addr & !0x1f_ffff;
+            tables
+                .l2
+                .map_table(block_2m, l3_base_addr as u64, TableAttrs::DEFAULT);
+            tables.l3.map_region(block_2m, block_2m, NORMAL_PAGE_ATTRS);
+            tables.l3.unmap_stack_guards(block_2m);
+        }
+
         // Enable MMU using the prepared translation tables
         MMU::enable_el2(tables.l0.base_addr() as u64);
 
@@ -115,10 +156,33 @@ fn main(info: EntryInfo) -> ! {
         set_max_level(log::LevelFilter::Info);
     }
 
-    info!("Hello World! cpu_idx = {}", info.cpu_idx);
+    info!(
+        "Hello World! cpu_idx = {}, fdt = {:#x}",
+        info.cpu_idx, info.arg0
+    );
 
-    // Start secondary core via PSCI syscall to ARM Trusted Firmware
-    Psci::cpu_on_64::<Smccc<SMC>>(1, (_secondary_start as *const fn() -> !) as u64, 0).unwrap();
+    // Print the platform description passed by the bootloader, if any
+    if info.arg0 != 0
+        && let Ok(fdt) = unsafe { Fdt::from_ptr(info.arg0 as *const u8) }
+    {
+        for region in fdt.memory() {
+            info!("memory: {:#x} size {:#x}", region.address, region.size);
+        }
+        let cpus: Vec<u64> = fdt.cpus().map(|cpu| cpu.mpidr).collect();
+        info!("cpus: {:x?}, psci: {:?}", cpus, fdt.psci());
+        info!("stdout: {:?}", fdt.stdout_path());
+    }
+
+    set_alloc_error_hook(|layout| error!("Out of memory: {:?}", layout));
+    info!("Heap: {:?}", heap_stats());
+
+    // Start the secondary cores via PSCI syscalls to ARM Trusted Firmware, with the cpu idx as context id
+    let smp = SmpManager::<Smccc<SMC>>::new((_secondary_start as *const fn() -> !) as u64);
+    if let Err(err) = smp.start_cpus((1..info.num_cores).map(|cpu_idx| (cpu_idx, cpu_idx as u64))) {
+        error!("Starting secondary cores failed: {:?}", err);
+    }
+    let online: Vec<usize> = online_cpus().collect();
+    info!("Online cpus: {:?}", online);
 
     loop {
         unsafe { core::arch::asm!("nop") };
@@ -137,7 +201,16 @@ fn main2(info: EntryInfo) -> ! {
         DCache::enable();
     });
 
-    info!("Hello World! cpu_idx = {}", info.cpu_idx);
+    info!(
+        "Hello World! cpu_idx = {}, context_id = {:#x}",
+        info.cpu_idx, info.arg0
+    );
+
+    // Warn if the stack came within 4K of its limit
+    let usage = check_stack_usage(info.cpu_idx, 0x1000, |cpu_idx, usage| {
+        warn!("cpu_idx = {} stack almost exhausted: {:?}", cpu_idx, usage);
+    });
+    info!("Stack usage: {} / {} bytes", usage.max_used, usage.size);
 
     loop {}
 }
@@ -146,6 +219,11 @@ fn main2(info: EntryInfo) -> ! {
 fn panic(info: &PanicInfo) -> ! {
     error!("PANIC: {:?}", info);
 
+    error!("backtrace:");
+    for (i, ret_addr) in Backtrace::current().take(32).enumerate() {
+        error!("  #{:<2} ra {:#018x}", i + 1, ret_addr);
+    }
+
     // Psci::system_reset::<Smccc<SMC>>().unwrap();
 
     loop {}
@@ -0,0 +1,286 @@
+#![no_std]
+
+mod node;
+mod platform;
+mod property;
+
+pub use node::*;
+pub use platform::*;
+pub use property::*;
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_VERSION: u32 = 17;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+const HEADER_SIZE: usize = 40;
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    BadMagic,
+    BadVersion,
+    Truncated,
+    BadStructure,
+}
+
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    structs: &'a [u8],
+    strings: &'a [u8],
+    mem_rsvmap: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, FdtError> {
+        if data.len() < HEADER_SIZE {
+            return Err(FdtError::Truncated);
+        }
+
+        if be32(data, 0) != Some(FDT_MAGIC) {
+            return Err(FdtError::BadMagic);
+        }
+
+        let header = |idx: usize| be32(data, idx * 4).unwrap() as usize;
+        let total_size = header(1);
+        let off_dt_struct = header(2);
+        let off_dt_strings = header(3);
+        let off_mem_rsvmap = header(4);
+        let last_comp_version = header(6);
+        let size_dt_strings = header(8);
+        let size_dt_struct = header(9);
+
+        if last_comp_version as u32 > FDT_VERSION {
+            return Err(FdtError::BadVersion);
+        }
+
+        let data = data.get(..total_size).ok_or(FdtError::Truncated)?;
+        let structs = data
+            .get(off_dt_struct..off_dt_struct + size_dt_struct)
+            .ok_or(FdtError::Truncated)?;
+        let strings = data
+            .get(off_dt_strings..off_dt_strings + size_dt_strings)
+            .ok_or(FdtError::Truncated)?;
+        let mem_rsvmap = data.get(off_mem_rsvmap..).ok_or(FdtError::Truncated)?;
+
+        let fdt = Self {
+            data,
+            structs,
+            strings,
+            mem_rsvmap,
+        };
+
+        // root() relies on a valid root node
+        match fdt.next_token(0) {
+            Some((FDT_BEGIN_NODE, offset)) if Node::new(fdt, offset, 2, 1).is_some() => Ok(fdt),
+            _ => Err(FdtError::BadStructure),
+        }
+    }
+
+    // Reads the blob size from the header, e.g. for the DTB pointer passed in x0 at boot
+    /// # Safety
+    /// `ptr` must point to a readable blob of at least the size given in its header.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Result<Self, FdtError> {
+        let header = unsafe { core::slice::from_raw_parts(ptr, HEADER_SIZE) };
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(FdtError::BadMagic);
+        }
+
+        let total_size = be32(header, 4).unwrap() as usize;
+        Self::new(unsafe { core::slice::from_raw_parts(ptr, total_size) })
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn boot_cpuid_phys(&self) -> u32 {
+        be32(self.data, 28).unwrap()
+    }
+
+    pub fn root(&self) -> Node<'a> {
+        let (_, offset) = self.next_token(0).unwrap();
+        Node::new(*self, offset, 2, 1).unwrap()
+    }
+
+    pub fn nodes(&self) -> Nodes<'a> {
+        Nodes::new(*self)
+    }
+
+    // Path components without unit address match any unit address, e.g. "/memory" matches "/memory@40000000"
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        let mut node = self.root();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            node = node.children().find(|child| child.name_matches(name))?;
+        }
+
+        Some(node)
+    }
+
+    pub fn find_phandle(&self, phandle: u32) -> Option<Node<'a>> {
+        self.nodes().find(|node| node.phandle() == Some(phandle))
+    }
+
+    pub fn find_compatible(&self, compatible: &[&str]) -> Option<Node<'a>> {
+        self.nodes()
+            .find(|node| compatible.iter().any(|c| node.is_compatible(c)))
+    }
+
+    pub fn mem_reservations(&self) -> MemReservations<'a> {
+        MemReservations {
+            data: self.mem_rsvmap,
+            offset: 0,
+        }
+    }
+
+    // Returns (token, offset after token) skipping NOPs
+    fn next_token(&self, mut offset: usize) -> Option<(u32, usize)> {
+        loop {
+            let token = be32(self.structs, offset)?;
+            offset += 4;
+            if token != FDT_NOP {
+                return Some((token, offset));
+            }
+        }
+    }
+
+    // Offset after the NUL terminated name at offset, 4 byte aligned
+    fn node_name(&self, offset: usize) -> Option<(&'a str, usize)> {
+        let bytes = self.structs.get(offset..)?;
+        let len = bytes.iter().position(|b| *b == 0)?;
+        let name = core::str::from_utf8(&bytes[..len]).ok()?;
+        Some((name, align4(offset + len + 1)))
+    }
+
+    // Returns the property and the offset after it, offset must point after a FDT_PROP token
+    fn property(&self, offset: usize) -> Option<(Property<'a>, usize)> {
+        let len = be32(self.structs, offset)? as usize;
+        let name_offset = be32(self.structs, offset + 4)? as usize;
+        let value = self.structs.get(offset + 8..offset + 8 + len)?;
+
+        let name = self.strings.get(name_offset..)?;
+        let name_len = name.iter().position(|b| *b == 0)?;
+        let name = core::str::from_utf8(&name[..name_len]).ok()?;
+
+        Some((Property::new(name, value), align4(offset + 8 + len)))
+    }
+
+    // Offset after the FDT_END_NODE of the node whose properties start at offset
+    fn skip_node(&self, mut offset: usize) -> Option<usize> {
+        let mut depth = 1;
+        loop {
+            let (token, next) = self.next_token(offset)?;
+            offset = match token {
+                FDT_BEGIN_NODE => {
+                    depth += 1;
+                    self.node_name(next)?.1
+                }
+                FDT_END_NODE => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(next);
+                    }
+                    next
+                }
+                FDT_PROP => self.property(next)?.1,
+                _ => return None,
+            };
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub address: u64,
+    pub size: u64,
+}
+
+pub struct MemReservations<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for MemReservations<'a> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let address = be64(self.data, self.offset)?;
+        let size = be64(self.data, self.offset + 8)?;
+        if address == 0 && size == 0 {
+            return None;
+        }
+
+        self.offset += 16;
+        Some(Region { address, size })
+    }
+}
+
+// Depth first walk over all nodes, cells of the parents are kept on a fixed size stack
+pub struct Nodes<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+    depth: usize,
+    cells: [(u32, u32); MAX_DEPTH],
+}
+
+impl<'a> Nodes<'a> {
+    fn new(fdt: Fdt<'a>) -> Self {
+        Self {
+            fdt,
+            offset: 0,
+            depth: 0,
+            cells: [(2, 1); MAX_DEPTH],
+        }
+    }
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (token, next) = self.fdt.next_token(self.offset)?;
+            match token {
+                FDT_BEGIN_NODE => {
+                    if self.depth >= MAX_DEPTH {
+                        return None;
+                    }
+
+                    let (address_cells, size_cells) = self.cells[self.depth.saturating_sub(1)];
+                    let node = Node::new(self.fdt, next, address_cells, size_cells)?;
+
+                    self.cells[self.depth] = node.child_cells();
+                    self.depth += 1;
+                    self.offset = node.props_offset();
+                    return Some(node);
+                }
+                FDT_END_NODE => {
+                    self.depth = self.depth.checked_sub(1)?;
+                    self.offset = next;
+                }
+                FDT_PROP => self.offset = self.fdt.property(next)?.1,
+                FDT_END => return None,
+                _ => return None,
+            }
+        }
+    }
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn be64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
@@ -0,0 +1,199 @@
+use super::*;
+
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    name: &'a str,
+    props_offset: usize,
+    // Cells of the parent, used to decode reg
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl<'a> Node<'a> {
+    pub(crate) fn new(
+        fdt: Fdt<'a>,
+        offset: usize,
+        address_cells: u32,
+        size_cells: u32,
+    ) -> Option<Self> {
+        let (name, props_offset) = fdt.node_name(offset)?;
+        Some(Self {
+            fdt,
+            name,
+            props_offset,
+            address_cells,
+            size_cells,
+        })
+    }
+
+    pub(crate) fn props_offset(&self) -> usize {
+        self.props_offset
+    }
+
+    pub(crate) fn child_cells(&self) -> (u32, u32) {
+        let address_cells = self.property("#address-cells").and_then(|p| p.as_u32());
+        let size_cells = self.property("#size-cells").and_then(|p| p.as_u32());
+        (address_cells.unwrap_or(2), size_cells.unwrap_or(1))
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    // Name without the unit address
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap_or_default()
+    }
+
+    pub fn unit_address(&self) -> Option<&'a str> {
+        self.name.split_once('@').map(|(_, addr)| addr)
+    }
+
+    pub(crate) fn name_matches(&self, name: &str) -> bool {
+        if name.contains('@') {
+            self.name == name
+        } else {
+            self.base_name() == name
+        }
+    }
+
+    pub fn properties(&self) -> Properties<'a> {
+        Properties {
+            fdt: self.fdt,
+            offset: self.props_offset,
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|p| p.name() == name)
+    }
+
+    pub fn children(&self) -> Children<'a> {
+        let (address_cells, size_cells) = self.child_cells();
+        Children {
+            fdt: self.fdt,
+            offset: self.props_offset,
+            address_cells,
+            size_cells,
+        }
+    }
+
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle")
+            .or_else(|| self.property("linux,phandle"))
+            .and_then(|p| p.as_u32())
+    }
+
+    pub fn compatible(&self) -> StrList<'a> {
+        self.property("compatible")
+            .map(|p| p.as_str_list())
+            .unwrap_or_default()
+    }
+
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible().any(|c| c == compatible)
+    }
+
+    pub fn device_type(&self) -> Option<&'a str> {
+        self.property("device_type").and_then(|p| p.as_str())
+    }
+
+    // Disabled nodes have status "disabled", a missing status means okay
+    pub fn is_enabled(&self) -> bool {
+        match self.property("status").and_then(|p| p.as_str()) {
+            Some(status) => status == "okay" || status == "ok",
+            None => true,
+        }
+    }
+
+    pub fn reg(&self) -> Reg<'a> {
+        Reg {
+            cells: self
+                .property("reg")
+                .map(|p| p.as_cells())
+                .unwrap_or_default(),
+            address_cells: self.address_cells,
+            size_cells: self.size_cells,
+        }
+    }
+
+    pub fn interrupts(&self) -> Cells<'a> {
+        self.property("interrupts")
+            .map(|p| p.as_cells())
+            .unwrap_or_default()
+    }
+}
+
+impl core::fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Node").field("name", &self.name).finish()
+    }
+}
+
+pub struct Properties<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+}
+
+impl<'a> Iterator for Properties<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.fdt.next_token(self.offset)? {
+            (FDT_PROP, next) => {
+                let (property, next) = self.fdt.property(next)?;
+                self.offset = next;
+                Some(property)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct Children<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (token, next) = self.fdt.next_token(self.offset)?;
+            match token {
+                FDT_PROP => self.offset = self.fdt.property(next)?.1,
+                FDT_BEGIN_NODE => {
+                    let node = Node::new(self.fdt, next, self.address_cells, self.size_cells)?;
+                    self.offset = self.fdt.skip_node(node.props_offset)?;
+                    return Some(node);
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+pub struct Reg<'a> {
+    cells: Cells<'a>,
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl<'a> Iterator for Reg<'a> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Without cells no entry consumes any of reg
+        if self.cells.is_empty() || (self.address_cells == 0 && self.size_cells == 0) {
+            return None;
+        }
+
+        let address = self.cells.read(self.address_cells)?;
+        let size = self.cells.read(self.size_cells)?;
+        Some(Region { address, size })
+    }
+}
@@ -0,0 +1,229 @@
+use super::*;
+
+const PSCI_COMPATIBLE: &[&str] = &["arm,psci-1.0", "arm,psci-0.2", "arm,psci"];
+const GIC_V2_COMPATIBLE: &[&str] = &[
+    "arm,gic-400",
+    "arm,cortex-a15-gic",
+    "arm,cortex-a9-gic",
+    "arm,cortex-a7-gic",
+];
+const GIC_V3_COMPATIBLE: &[&str] = &["arm,gic-v3"];
+const TIMER_COMPATIBLE: &[&str] = &["arm,armv8-timer", "arm,armv7-timer"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnableMethod {
+    Psci,
+    SpinTable { release_addr: u64 },
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cpu<'a> {
+    pub node: Node<'a>,
+    pub mpidr: u64,
+    pub enable_method: EnableMethod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsciMethod {
+    Smc,
+    Hvc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GicVersion {
+    V2,
+    V3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gic {
+    pub version: GicVersion,
+    pub distributor: Region,
+    // GICC for v2, optional for v3
+    pub cpu_interface: Option<Region>,
+    // First GICR region, v3 only
+    pub redistributor: Option<Region>,
+    pub maintenance_irq: Option<Interrupt>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptKind {
+    Spi,
+    Ppi,
+    Other(u32),
+}
+
+impl From<u32> for InterruptKind {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Spi,
+            1 => Self::Ppi,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+// GIC 3 cell interrupt specifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupt {
+    pub kind: InterruptKind,
+    pub number: u32,
+    pub flags: u32,
+}
+
+impl Interrupt {
+    pub fn from_cells(cells: &mut Cells) -> Option<Self> {
+        Some(Self {
+            kind: cells.next()?.into(),
+            number: cells.next()?,
+            flags: cells.next()?,
+        })
+    }
+
+    pub fn intid(&self) -> Option<u32> {
+        match self.kind {
+            InterruptKind::Spi => Some(self.number + 32),
+            InterruptKind::Ppi => Some(self.number + 16),
+            InterruptKind::Other(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timer {
+    pub secure_phys: Interrupt,
+    pub non_secure_phys: Interrupt,
+    pub virt: Interrupt,
+    pub hyp_phys: Interrupt,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReservedMemory<'a> {
+    pub node: Node<'a>,
+    pub region: Region,
+    pub no_map: bool,
+}
+
+impl<'a> Fdt<'a> {
+    pub fn memory(&self) -> impl Iterator<Item = Region> + 'a {
+        self.root()
+            .children()
+            .filter(|node| node.device_type() == Some("memory") && node.is_enabled())
+            .flat_map(|node| node.reg())
+    }
+
+    pub fn cpus(&self) -> impl Iterator<Item = Cpu<'a>> + 'a {
+        self.find_node("/cpus")
+            .into_iter()
+            .flat_map(|cpus| cpus.children())
+            .filter(|node| node.device_type() == Some("cpu") && node.is_enabled())
+            .filter_map(|node| {
+                let mpidr = node.reg().next()?.address;
+                let method = node.property("enable-method").and_then(|p| p.as_str());
+                let enable_method = match method {
+                    Some("psci") => EnableMethod::Psci,
+                    Some("spin-table") => {
+                        match node.property("cpu-release-addr").and_then(|p| p.as_u64()) {
+                            Some(release_addr) => EnableMethod::SpinTable { release_addr },
+                            None => EnableMethod::Other,
+                        }
+                    }
+                    _ => EnableMethod::Other,
+                };
+
+                Some(Cpu {
+                    node,
+                    mpidr,
+                    enable_method,
+                })
+            })
+    }
+
+    pub fn psci(&self) -> Option<PsciMethod> {
+        let node = self.find_compatible(PSCI_COMPATIBLE)?;
+        match node.property("method")?.as_str()? {
+            "smc" => Some(PsciMethod::Smc),
+            "hvc" => Some(PsciMethod::Hvc),
+            _ => None,
+        }
+    }
+
+    pub fn gic(&self) -> Option<Gic> {
+        let node = self.find_compatible(GIC_V2_COMPATIBLE);
+        let (node, version) = match node {
+            Some(node) => (node, GicVersion::V2),
+            None => (self.find_compatible(GIC_V3_COMPATIBLE)?, GicVersion::V3),
+        };
+
+        let mut reg = node.reg();
+        let distributor = reg.next()?;
+        let (cpu_interface, redistributor) = match version {
+            GicVersion::V2 => (Some(reg.next()?), None),
+            GicVersion::V3 => {
+                // GICC follows all redistributor regions
+                let regions = node
+                    .property("#redistributor-regions")
+                    .and_then(|p| p.as_u32())
+                    .unwrap_or(1);
+                let redistributor = reg.next();
+                (reg.nth(regions.saturating_sub(1) as usize), redistributor)
+            }
+        };
+
+        Some(Gic {
+            version,
+            distributor,
+            cpu_interface,
+            redistributor,
+            maintenance_irq: Interrupt::from_cells(&mut node.interrupts()),
+        })
+    }
+
+    pub fn timer(&self) -> Option<Timer> {
+        let node = self.find_compatible(TIMER_COMPATIBLE)?;
+        let mut cells = node.interrupts();
+
+        Some(Timer {
+            secure_phys: Interrupt::from_cells(&mut cells)?,
+            non_secure_phys: Interrupt::from_cells(&mut cells)?,
+            virt: Interrupt::from_cells(&mut cells)?,
+            hyp_phys: Interrupt::from_cells(&mut cells)?,
+        })
+    }
+
+    // stdout-path without the ":options" suffix, may be an alias
+    pub fn stdout_path(&self) -> Option<&'a str> {
+        let path = self
+            .find_node("/chosen")?
+            .property("stdout-path")?
+            .as_str()?;
+        path.split(':').next()
+    }
+
+    pub fn stdout(&self) -> Option<Node<'a>> {
+        let path = self.stdout_path()?;
+        if path.starts_with('/') {
+            return self.find_node(path);
+        }
+
+        let alias = self.find_node("/aliases")?.property(path)?.as_str()?;
+        self.find_node(alias)
+    }
+
+    // Static /reserved-memory regions, dynamically allocated ones without reg are skipped
+    pub fn reserved_memory(&self) -> impl Iterator<Item = ReservedMemory<'a>> + 'a {
+        self.find_node("/reserved-memory")
+            .into_iter()
+            .flat_map(|reserved| reserved.children())
+            .filter(|node| node.is_enabled())
+            .flat_map(|node| {
+                let no_map = node.property("no-map").is_some();
+                node.reg().map(move |region| ReservedMemory {
+                    node,
+                    region,
+                    no_map,
+                })
+            })
+    }
+}
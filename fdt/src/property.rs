@@ -0,0 +1,109 @@
+#[derive(Clone, Copy)]
+pub struct Property<'a> {
+    name: &'a str,
+    value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    pub(crate) fn new(name: &'a str, value: &'a [u8]) -> Self {
+        Self { name, value }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.value.try_into().ok()?))
+    }
+
+    // Accepts one or two cells
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => self.as_u32().map(u64::from),
+            8 => Some(u64::from_be_bytes(self.value.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        let (last, bytes) = self.value.split_last()?;
+        if *last != 0 {
+            return None;
+        }
+
+        core::str::from_utf8(bytes).ok()
+    }
+
+    pub fn as_str_list(&self) -> StrList<'a> {
+        StrList { data: self.value }
+    }
+
+    pub fn as_cells(&self) -> Cells<'a> {
+        Cells { data: self.value }
+    }
+}
+
+impl core::fmt::Debug for Property<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Property")
+            .field("name", &self.name)
+            .field("len", &self.value.len())
+            .finish()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct StrList<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for StrList<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.data.iter().position(|b| *b == 0)?;
+        let s = core::str::from_utf8(&self.data[..len]).ok();
+        self.data = &self.data[len + 1..];
+        s
+    }
+}
+
+// Big endian 32 bit cells of a property value
+#[derive(Clone, Default)]
+pub struct Cells<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cells<'a> {
+    // Reads a number made of `cells` cells, at most 2 cells are supported
+    pub fn read(&mut self, cells: u32) -> Option<u64> {
+        let len = cells as usize * 4;
+        if cells > 2 || self.data.len() < len {
+            return None;
+        }
+
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Some(value.chunks(4).fold(0, |acc, c| {
+            (acc << 32) | u32::from_be_bytes(c.try_into().unwrap()) as u64
+        }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl<'a> Iterator for Cells<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read(1).map(|v| v as u32)
+    }
+}
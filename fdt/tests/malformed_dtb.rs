@@ -0,0 +1,149 @@
+use fdt::*;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_END: u32 = 0x9;
+
+// Minimal blob: header, empty memory reservation map, structure block, strings block
+fn dtb(structs: &[u8], strings: &[u8]) -> Vec<u8> {
+    let off_mem_rsvmap = 40;
+    let off_dt_struct = off_mem_rsvmap + 16;
+    let off_dt_strings = off_dt_struct + structs.len();
+    let total_size = off_dt_strings + strings.len();
+
+    let header = [
+        0xd00dfeed,
+        total_size as u32,
+        off_dt_struct as u32,
+        off_dt_strings as u32,
+        off_mem_rsvmap as u32,
+        17,
+        16,
+        0,
+        strings.len() as u32,
+        structs.len() as u32,
+    ];
+
+    let mut data: Vec<u8> = header.iter().flat_map(|v| v.to_be_bytes()).collect();
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(structs);
+    data.extend_from_slice(strings);
+    data
+}
+
+fn token(structs: &mut Vec<u8>, token: u32) {
+    structs.extend_from_slice(&token.to_be_bytes());
+}
+
+fn begin_node(structs: &mut Vec<u8>, name: &str) {
+    token(structs, FDT_BEGIN_NODE);
+    structs.extend_from_slice(name.as_bytes());
+    structs.push(0);
+    structs.resize(structs.len().next_multiple_of(4), 0);
+}
+
+fn prop(structs: &mut Vec<u8>, name_offset: u32, value: &[u8]) {
+    token(structs, FDT_PROP);
+    structs.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    structs.extend_from_slice(&name_offset.to_be_bytes());
+    structs.extend_from_slice(value);
+    structs.resize(structs.len().next_multiple_of(4), 0);
+}
+
+#[test]
+fn malformed_root() {
+    // Root node name without NUL terminator
+    let mut structs = Vec::new();
+    token(&mut structs, FDT_BEGIN_NODE);
+    structs.extend_from_slice(b"root");
+
+    assert_eq!(
+        Fdt::new(&dtb(&structs, b"")).err(),
+        Some(FdtError::BadStructure)
+    );
+}
+
+#[test]
+fn zero_cells_reg() {
+    let strings = b"#address-cells\0#size-cells\0device_type\0reg\0";
+
+    let mut structs = Vec::new();
+    begin_node(&mut structs, "");
+    prop(&mut structs, 0, &0u32.to_be_bytes());
+    prop(&mut structs, 15, &0u32.to_be_bytes());
+    begin_node(&mut structs, "memory@0");
+    prop(&mut structs, 27, b"memory\0");
+    prop(&mut structs, 39, &[0; 8]);
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END);
+
+    let data = dtb(&structs, strings);
+    let fdt = Fdt::new(&data).unwrap();
+    assert_eq!(fdt.find_node("/memory").unwrap().name(), "memory@0");
+    assert_eq!(fdt.memory().count(), 0);
+}
+
+#[test]
+fn spin_table_without_release_addr() {
+    let strings = b"#address-cells\0#size-cells\0device_type\0reg\0enable-method\0";
+
+    let mut structs = Vec::new();
+    begin_node(&mut structs, "");
+    begin_node(&mut structs, "cpus");
+    prop(&mut structs, 0, &1u32.to_be_bytes());
+    prop(&mut structs, 15, &0u32.to_be_bytes());
+    begin_node(&mut structs, "cpu@1");
+    prop(&mut structs, 27, b"cpu\0");
+    prop(&mut structs, 39, &1u32.to_be_bytes());
+    prop(&mut structs, 43, b"spin-table\0");
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END);
+
+    let data = dtb(&structs, strings);
+    let fdt = Fdt::new(&data).unwrap();
+    let cpus: Vec<_> = fdt.cpus().collect();
+    assert_eq!(cpus.len(), 1);
+    assert_eq!(cpus[0].mpidr, 1);
+    assert_eq!(cpus[0].enable_method, EnableMethod::Other);
+}
+
+#[test]
+fn gic_v3_redistributor_regions() {
+    let strings = b"#address-cells\0#size-cells\0compatible\0#redistributor-regions\0reg\0";
+
+    let mut structs = Vec::new();
+    begin_node(&mut structs, "");
+    prop(&mut structs, 0, &1u32.to_be_bytes());
+    prop(&mut structs, 15, &1u32.to_be_bytes());
+    begin_node(&mut structs, "interrupt-controller@8000000");
+    prop(&mut structs, 27, b"arm,gic-v3\0");
+    prop(&mut structs, 38, &2u32.to_be_bytes());
+    let reg: Vec<u8> = [
+        0x0800_0000u32,
+        0x1_0000,
+        0x080a_0000,
+        0x2_0000,
+        0x0900_0000,
+        0x2_0000,
+        0x0801_0000,
+        0x1_0000,
+    ]
+    .iter()
+    .flat_map(|v| v.to_be_bytes())
+    .collect();
+    prop(&mut structs, 61, &reg);
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END_NODE);
+    token(&mut structs, FDT_END);
+
+    let data = dtb(&structs, strings);
+    let gic = Fdt::new(&data).unwrap().gic().unwrap();
+    assert_eq!(gic.version, GicVersion::V3);
+    assert_eq!(gic.distributor.address, 0x0800_0000);
+    assert_eq!(gic.redistributor.map(|r| r.address), Some(0x080a_0000));
+    assert_eq!(gic.cpu_interface.map(|r| r.address), Some(0x0801_0000));
+}
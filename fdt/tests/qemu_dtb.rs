@@ -0,0 +1,117 @@
+use fdt::*;
+
+static QEMU_DTB: &[u8] = include_bytes!("../../example/qemu.dtb");
+
+fn fdt() -> Fdt<'static> {
+    Fdt::new(QEMU_DTB).unwrap()
+}
+
+#[test]
+fn header() {
+    assert_eq!(fdt().total_size(), QEMU_DTB.len());
+    assert_eq!(Fdt::new(&QEMU_DTB[..16]).err(), Some(FdtError::Truncated));
+    assert_eq!(Fdt::new(&[0; 64]).err(), Some(FdtError::BadMagic));
+
+    let fdt = unsafe { Fdt::from_ptr(QEMU_DTB.as_ptr()) }.unwrap();
+    assert_eq!(fdt.root().name(), "");
+}
+
+#[test]
+fn find_node() {
+    let fdt = fdt();
+    assert_eq!(fdt.find_node("/memory").unwrap().name(), "memory@40000000");
+    assert_eq!(fdt.find_node("/cpus/cpu@2").unwrap().name(), "cpu@2");
+    assert_eq!(
+        fdt.find_node("/gpio-keys/poweroff")
+            .unwrap()
+            .property("label")
+            .unwrap()
+            .as_str(),
+        Some("GPIO Key Poweroff")
+    );
+    assert!(fdt.find_node("/cpus/cpu@4").is_none());
+
+    assert_eq!(fdt.find_phandle(0x8006).unwrap().name(), "v2m@8020000");
+    assert_eq!(
+        fdt.find_compatible(&["arm,pl011"]).unwrap().name(),
+        "pl011@9000000"
+    );
+    assert_eq!(
+        fdt.nodes()
+            .filter(|n| n.is_compatible("virtio,mmio"))
+            .count(),
+        32
+    );
+}
+
+#[test]
+fn memory() {
+    let memory: Vec<_> = fdt().memory().collect();
+    assert_eq!(
+        memory,
+        [Region {
+            address: 0x4000_0000,
+            size: 0x1_0000_0000
+        }]
+    );
+    assert_eq!(fdt().mem_reservations().count(), 0);
+    assert_eq!(fdt().reserved_memory().count(), 0);
+}
+
+#[test]
+fn cpus() {
+    let cpus: Vec<_> = fdt().cpus().collect();
+    assert_eq!(cpus.len(), 4);
+    for (i, cpu) in cpus.iter().enumerate() {
+        assert_eq!(cpu.mpidr, i as u64);
+        assert_eq!(cpu.enable_method, EnableMethod::Psci);
+    }
+}
+
+#[test]
+fn psci() {
+    assert_eq!(fdt().psci(), Some(PsciMethod::Smc));
+}
+
+#[test]
+fn gic() {
+    let gic = fdt().gic().unwrap();
+    assert_eq!(gic.version, GicVersion::V2);
+    assert_eq!(
+        gic.distributor,
+        Region {
+            address: 0x800_0000,
+            size: 0x1_0000
+        }
+    );
+    assert_eq!(
+        gic.cpu_interface,
+        Some(Region {
+            address: 0x801_0000,
+            size: 0x1_0000
+        })
+    );
+    assert_eq!(gic.redistributor, None);
+    assert_eq!(gic.maintenance_irq.unwrap().intid(), Some(25));
+}
+
+#[test]
+fn timer() {
+    let timer = fdt().timer().unwrap();
+    assert_eq!(timer.secure_phys.intid(), Some(29));
+    assert_eq!(timer.non_secure_phys.intid(), Some(30));
+    assert_eq!(timer.virt.intid(), Some(27));
+    assert_eq!(timer.hyp_phys.intid(), Some(26));
+    assert_eq!(timer.non_secure_phys.kind, InterruptKind::Ppi);
+    assert_eq!(timer.non_secure_phys.flags, 0xf04);
+}
+
+#[test]
+fn stdout() {
+    let fdt = fdt();
+    assert_eq!(fdt.stdout_path(), Some("/pl011@9000000"));
+
+    let stdout = fdt.stdout().unwrap();
+    assert!(stdout.is_compatible("arm,pl011"));
+    assert_eq!(stdout.reg().next().unwrap().address, 0x900_0000);
+}
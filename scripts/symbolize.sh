@@ -0,0 +1,37 @@
+#!/usr/bin/env bash
+# Symbolize backtraces printed by the crash dump / panic handler
+# usage: symbolize.sh <elf> [log]  (reads the log from stdin if omitted)
+
+set -euo pipefail
+
+if [ $# -lt 1 ]; then
+	echo "usage: $0 <elf> [log]" >&2
+	exit 1
+fi
+
+elf="$1"
+log="${2:-/dev/stdin}"
+
+addr2line="${ADDR2LINE:-}"
+if [ -z "$addr2line" ]; then
+	for tool in llvm-addr2line aarch64-unknown-none-elf-addr2line aarch64-none-elf-addr2line addr2line; do
+		if command -v "$tool" >/dev/null; then
+			addr2line="$tool"
+			break
+		fi
+	done
+fi
+
+if [ -z "$addr2line" ]; then
+	echo "no addr2line found, set ADDR2LINE" >&2
+	exit 1
+fi
+
+grep -oE '#[0-9]+ +(pc|ra) 0x[0-9a-fA-F]+' "$log" | while read -r idx kind addr; do
+	# Return addresses point after the call, symbolize the call instruction instead
+	if [ "$kind" = "ra" ]; then
+		addr=$(printf '0x%016x' $((addr - 4)))
+	fi
+
+	printf '%-4s %s %s\n' "$idx" "$addr" "$("$addr2line" -e "$elf" -f -C -i -p "$addr" | tr '\n' ' ')"
+done